@@ -1,17 +1,41 @@
-use super::HEIGHT;
-use super::WIDTH;
-
 mod data_types;
 mod map_renderer;
+mod bsp_debug;
 mod player;
 mod wad_data;
 mod wad_reader;
 mod bsp;
+mod maps;
+mod blockmap;
+mod reject;
+mod palette;
+mod textures;
+mod view_renderer;
+mod visplanes;
+mod sprites;
+mod lighting;
+mod frame_buffer;
+mod game;
+mod fixed;
+mod angle;
+mod tables;
+
+use std::io::{Read, Seek};
 
+use angle::Angle;
+use data_types::Thing;
+use fixed::Fixed;
 use player::Player;
-use wad_data::WadData;
+pub use wad_data::WadData;
 use bsp::BSP;
+use view_renderer::ViewRenderer;
+pub use map_renderer::Automap;
+pub use bsp_debug::BspDebug;
+pub use wad_reader::WadError;
+pub use frame_buffer::{FrameBuffer, PixelFormat};
+pub use game::{GameMode, Skill};
 
+#[derive(Clone, Copy)]
 pub struct KeyState {
     pub up:bool,
     pub down:bool,
@@ -21,23 +45,61 @@ pub struct KeyState {
     pub a: bool,
     pub s: bool,
     pub d: bool,
+    pub zoom_in: bool,
+    pub zoom_out: bool,
 }
 
 pub struct DoomEngine {
     wad_data: WadData,
     player: Player,
+    /// Things of the map placed for the skill and game mode.
+    things: Vec<Thing>,
+    bsp: BSP,
     pub key_state: KeyState,
+    /// Drawn instead of the first-person view while active.
+    pub automap: Automap,
+    /// Replays the BSP traversal of the view over the automap while active.
+    pub bsp_debug: BspDebug,
+    /// Palette-indexed frame, converted through `palette` when presented.
+    screen: Vec<u8>,
+    palette: usize,
 }
 
 impl DoomEngine {
-    /// Create a new `World` instance that can draw a moving box.
-    pub fn new() -> Self {
-        let wad_data = WadData::new("./doom1.wad", "E1M1\0\0\0\0");
+    /// Loads a map, without things or player until `start_game` is called.
+    /// Maps without a player 1 start are rejected, as in the original game.
+    pub fn new(iwad: &str, pwads: &[String], map_name: Option<&str>) -> Result<Self, WadError> {
+        let wad_data = WadData::new(iwad, pwads, map_name)?;
+        Ok(DoomEngine::with_wad_data(wad_data))
+    }
+
+    /// Loads a map like `new` from a WAD held in memory, e.g. one downloaded
+    /// or built by a test. `name` is only used in error messages.
+    pub fn from_bytes(name: &str, bytes: Vec<u8>, map_name: Option<&str>) -> Result<Self, WadError> {
+        let reader = wad_reader::WadReader::from_bytes(name, bytes)?;
+        let wad_data = WadData::from_reader(reader, map_name)?;
+        Ok(DoomEngine::with_wad_data(wad_data))
+    }
+
+    /// Loads a map like `new` from a WAD read from any seekable source, e.g.
+    /// an entry of an archive. `name` is only used in error messages.
+    pub fn from_reader<R: Read + Seek + 'static>(name: &str, source: R, map_name: Option<&str>) -> Result<Self, WadError> {
+        let reader = wad_reader::WadReader::from_reader(name, source)?;
+        let wad_data = WadData::from_reader(reader, map_name)?;
+        Ok(DoomEngine::with_wad_data(wad_data))
+    }
+
+    /// Geometry, lookup tables and graphics of the loaded map.
+    pub fn wad_data(&self) -> &WadData {
+        &self.wad_data
+    }
+
+    fn with_wad_data(wad_data: WadData) -> Self {
         let player = Player {
-            pos: wad_data.things[0].pos,
-            angle: wad_data.things[0].angle,
+            pos: (Fixed::ZERO, Fixed::ZERO),
+            angle: Angle(0),
         };
-        let bsp = BSP {root_node_id: wad_data.nodes.len()-1};
+        let bsp = BSP::new(&wad_data.nodes);
         let key_state = KeyState{
             left:false,
             right:false,
@@ -47,102 +109,122 @@ impl DoomEngine {
             a: false,
             s: false,
             d: false,
+            zoom_in: false,
+            zoom_out: false,
         };
-        DoomEngine { wad_data, player, key_state }
+        let automap = Automap::new(&wad_data);
+        DoomEngine {
+            wad_data,
+            player,
+            things: Vec::new(),
+            bsp,
+            key_state,
+            automap,
+            bsp_debug: BspDebug::default(),
+            screen: Vec::new(),
+            palette: 0,
+        }
+    }
+
+    /// Places the things of the map present at `skill` in a game of `mode`,
+    /// and puts the player on its start.
+    pub fn start_game(&mut self, skill: Skill, mode: GameMode) {
+        self.things = self
+            .wad_data
+            .things
+            .iter()
+            .filter(|thing| thing.spawns_in(skill, mode))
+            .cloned()
+            .collect();
+        let start = self.wad_data.player_start(mode);
+        self.player.pos = (start.pos.0.into(), start.pos.1.into());
+        self.player.angle = Angle::from_map_angle(start.angle);
     }
 
      
     pub fn update(&mut self) {
-        self.player.update(&self.key_state);
+        let mut key_state = self.key_state;
+        if self.automap.active {
+            self.automap.update(&self.key_state, &self.player);
+            // The arrow keys pan the map instead of turning the player.
+            if !self.automap.follow {
+                key_state.left = false;
+                key_state.right = false;
+            }
+        }
+        self.player.update(&key_state);
     }
 
-    /// Draw the `World` state to the frame buffer.
-    ///
-    /// Assumes the default texture format: `wgpu::TextureFormat::Rgba8UnormSrgb`
-    // min_x, min_y, max_x, max_y
-    pub fn draw(&self, frame: &mut [u8]) {
-        clear(frame);
-        self.draw_lines(frame);
-        self.draw_player(frame);
-        self.draw_nodes(frame);
-        
-        
+    /// Moves the player to a map position, facing `angle` degrees.
+    pub fn place_player(&mut self, pos: (i16, i16), angle: i16) {
+        self.player.pos = (pos.0.into(), pos.1.into());
+        self.player.angle = Angle::from_degrees(angle as f32);
     }
 
-    fn draw_lines(&self, frame: &mut[u8]){
-        for l in self.wad_data.linedefs.iter() {
-            let v1 = l.start_vertex_id as usize;
-            let v2 = l.end_vertex_id as usize;
-            let x0 = self.wad_data.vertexes[v1].x;
-            let y0 = self.wad_data.vertexes[v1].y;
-            let x1 = self.wad_data.vertexes[v2].x;
-            let y1 = self.wad_data.vertexes[v2].y;
-            for (x, y) in line_drawing::Bresenham::new((x0, y0), (x1, y1)) {
-                plot(x, y, frame, (255,255,255));
+    /// Moves the player onto the thing at `thing_index` in THINGS, facing the
+    /// same way. Returns false if the map has no such thing.
+    pub fn place_player_at_thing(&mut self, thing_index: usize) -> bool {
+        match self.wad_data.things.get(thing_index) {
+            Some(thing) => {
+                self.player.pos = (thing.pos.0.into(), thing.pos.1.into());
+                self.player.angle = Angle::from_map_angle(thing.angle);
+                true
             }
+            None => false,
         }
     }
-    fn draw_player(&self, frame: &mut [u8]){
-        let cx = self.player.pos.0  ;
-        let cy =  self.player.pos.1 ;
-        for (x, y) in line_drawing::BresenhamCircle::new(cx, cy, 32) {
-            plot(x,y,frame,(255,0,255));
-        }
+
+    /// Turns the player counterclockwise by `degrees`.
+    pub fn turn_player(&mut self, degrees: i16) {
+        self.player.angle += Angle::from_degrees(degrees as f32);
     }
-    fn draw_nodes(&self, frame: &mut [u8]){
-        //for n in self.wad_data.nodes.iter() {
-            let n = &self.wad_data.nodes[self.wad_data.nodes.len()-1];
-            let y0 = n.bbox_front.top;
-            let x0 = n.bbox_front.left;
-            let y1 = n.bbox_front.bottom;
-            let x1 = n.bbox_front.right;
-            for (x, y) in line_drawing::Bresenham::new((x0, y0), (x1, y0)) {
-                plot(x, y, frame, (255,0,255));
-            }
-            for (x, y) in line_drawing::Bresenham::new((x1, y0), (x1, y1)) {
-                plot(x, y, frame, (255,0,255));
-            }
-            for (x, y) in line_drawing::Bresenham::new((x1, y1), (x0, y1)) {
-                plot(x, y, frame, (255,0,255));
-            }
-            for (x, y) in line_drawing::Bresenham::new((x0, y1), (x0, y0)) {
-                plot(x, y, frame, (255,0,255));
-            } 
-            let y0 = n.bbox_back.top;
-            let x0 = n.bbox_back.left;
-            let y1 = n.bbox_back.bottom;
-            let x1 = n.bbox_back.right;
-            for (x, y) in line_drawing::Bresenham::new((x0, y0), (x1, y0)) {
-                plot(x, y, frame, (128,128,0));
-            }
-            for (x, y) in line_drawing::Bresenham::new((x1, y0), (x1, y1)) {
-                plot(x, y, frame, (128,128,0));
-            }
-            for (x, y) in line_drawing::Bresenham::new((x1, y1), (x0, y1)) {
-                plot(x, y, frame, (128,128,0));
+
+    /// Selects the PLAYPAL palette used to present the next frames: 0 is the
+    /// normal one, 1 to 8 the red damage flashes, 9 to 12 the gold bonus
+    /// flashes and 13 the radiation suit. Out of range palettes select the last.
+    pub fn set_palette(&mut self, palette: usize) {
+        self.palette = palette.min(self.wad_data.palettes.len() - 1);
+    }
+
+    /// Draw the `World` state to the frame buffer, at the frame's resolution.
+    pub fn draw(&mut self, frame: &mut FrameBuffer) {
+        let mut pixels = std::mem::take(&mut self.screen);
+        pixels.resize(frame.width * frame.height, 0);
+
+        let mut screen = FrameBuffer::new(&mut pixels, frame.width, frame.height, PixelFormat::Indexed8);
+        clear(&mut screen);
+        if self.automap.active {
+            if self.bsp_debug.active {
+                // The view is rendered only to record its BSP traversal.
+                self.draw_view(screen);
+                screen = FrameBuffer::new(&mut pixels, frame.width, frame.height, PixelFormat::Indexed8);
+                clear(&mut screen);
             }
-            for (x, y) in line_drawing::Bresenham::new((x0, y1), (x0, y0)) {
-                plot(x, y, frame, (128,128,0));
-            } 
-       //}
+            let bsp_debug = self.bsp_debug.active.then_some(&self.bsp_debug);
+            self.automap.draw(&mut screen, &self.wad_data, &self.player, bsp_debug);
+        } else {
+            self.draw_view(screen);
+        }
+
+        let screen = FrameBuffer::new(&mut pixels, frame.width, frame.height, PixelFormat::Indexed8);
+        frame.present(&screen, &self.wad_data.palettes[self.palette]);
+        self.screen = pixels;
     }
-}
 
-fn plot(x: i16, y: i16, frame: &mut [u8], col:(u8,u8,u8)) {
-    let sx = x as i32 / 8 + 160;
-    let sy = -250 - y as i32 / 8;
-    let p = (sx * 4 + sy * WIDTH as i32 * 4) as usize;
-    if p > 0 && p < (WIDTH * HEIGHT * 4) as usize {
-        frame[p] = col.0;
-        frame[p + 1] = col.1;
-        frame[p + 2] = col.2;
-        frame[p + 3] = 255;
+    fn draw_view(&mut self, screen: FrameBuffer) {
+        let mut view = ViewRenderer::new(&self.wad_data, &self.player, &self.things, &self.bsp, screen);
+        if self.bsp_debug.active {
+            view.bsp_trace = Some(Vec::new());
+        }
+        view.render(&self.bsp);
+        self.automap.mark_seen(&view.seen_lines);
+        if let Some(trace) = view.bsp_trace.take() {
+            self.bsp_debug.set_trace(trace);
+        }
     }
 }
 
  /// Clear the screen
-fn clear(screen: &mut [u8]) {
-    for (i, byte) in screen.iter_mut().enumerate() {
-        *byte = if i % 4 == 3 { 255 } else { 0 };
-    }
+fn clear(screen: &mut FrameBuffer) {
+    screen.fill(0);
 }
\ No newline at end of file
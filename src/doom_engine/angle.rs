@@ -0,0 +1,176 @@
+use std::ops::{Add, AddAssign, Neg, Sub, SubAssign};
+
+use super::fixed::Fixed;
+use super::tables::{FINE_ANGLES, FINE_SINE, FINE_TANGENT, SLOPE_RANGE, TAN_TO_ANGLE};
+
+pub const ANG45: u32 = 0x2000_0000;
+pub const ANG90: u32 = 0x4000_0000;
+pub const ANG180: u32 = 0x8000_0000;
+pub const ANG270: u32 = 0xC000_0000;
+pub const ANG1: u32 = ANG45 / 45;
+
+/// Shift from a binary angle to a fine angle.
+pub const ANGLE_TO_FINE_SHIFT: u32 = 19;
+
+/// A binary angle measure: a full turn is 2^32, so angles wrap around like
+/// the unsigned integers holding them. 0 faces east, `ANG90` north.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Default)]
+pub struct Angle(pub u32);
+
+impl Angle {
+    pub fn from_degrees(degrees: f32) -> Angle {
+        let turns = (degrees as f64 / 360.0).rem_euclid(1.0);
+        Angle((turns * 4_294_967_296.0) as u64 as u32)
+    }
+
+    /// Angle of a thing placed in a map. As in the original engine, only
+    /// multiples of 45 degrees are kept.
+    pub fn from_map_angle(degrees: i16) -> Angle {
+        Angle(ANG45.wrapping_mul((degrees / 45) as u32))
+    }
+
+    /// Angle stored in a seg, which keeps the top 16 bits.
+    pub fn from_seg_angle(angle: i16) -> Angle {
+        Angle((angle as u16 as u32) << 16)
+    }
+
+    /// Degrees in `0..360`.
+    pub fn to_degrees(self) -> f32 {
+        (self.0 as f64 * 360.0 / 4_294_967_296.0) as f32
+    }
+
+    /// Index into the fine tables.
+    pub fn fine(self) -> usize {
+        (self.0 >> ANGLE_TO_FINE_SHIFT) as usize
+    }
+
+    pub fn sin(self) -> Fixed {
+        Fixed(FINE_SINE[self.fine()])
+    }
+
+    pub fn cos(self) -> Fixed {
+        Fixed(FINE_SINE[self.fine() + FINE_ANGLES / 4])
+    }
+
+    /// Tangent of an angle in `-ANG90..ANG90`.
+    pub fn tan(self) -> Fixed {
+        Fixed(FINE_TANGENT[(self.0.wrapping_add(ANG90) >> ANGLE_TO_FINE_SHIFT) as usize & (FINE_ANGLES / 2 - 1)])
+    }
+
+    /// Angle of the vector `(dx, dy)`, looked up by octant like
+    /// `R_PointToAngle`.
+    pub fn of_vector(dx: Fixed, dy: Fixed) -> Angle {
+        let (x, y) = (dx.0, dy.0);
+        if x == 0 && y == 0 {
+            return Angle(0);
+        }
+        let (ax, ay) = (x.unsigned_abs(), y.unsigned_abs());
+        let angle = match (x >= 0, y >= 0, ax > ay) {
+            (true, true, true) => TAN_TO_ANGLE[slope_div(ay, ax)],
+            (true, true, false) => (ANG90 - 1).wrapping_sub(TAN_TO_ANGLE[slope_div(ax, ay)]),
+            (false, true, false) => ANG90.wrapping_add(TAN_TO_ANGLE[slope_div(ax, ay)]),
+            (false, true, true) => (ANG180 - 1).wrapping_sub(TAN_TO_ANGLE[slope_div(ay, ax)]),
+            (false, false, true) => ANG180.wrapping_add(TAN_TO_ANGLE[slope_div(ay, ax)]),
+            (false, false, false) => (ANG270 - 1).wrapping_sub(TAN_TO_ANGLE[slope_div(ax, ay)]),
+            (true, false, false) => ANG270.wrapping_add(TAN_TO_ANGLE[slope_div(ax, ay)]),
+            (true, false, true) => TAN_TO_ANGLE[slope_div(ay, ax)].wrapping_neg(),
+        };
+        Angle(angle)
+    }
+}
+
+/// Index into `TAN_TO_ANGLE` of the slope `num / den`, for `num <= den`.
+fn slope_div(num: u32, den: u32) -> usize {
+    if den < 512 {
+        return SLOPE_RANGE as usize;
+    }
+    // The shift wraps for large numerators, as it did in the original.
+    let slope = num.wrapping_shl(3) / (den >> 8);
+    slope.min(SLOPE_RANGE) as usize
+}
+
+impl Add for Angle {
+    type Output = Angle;
+    fn add(self, other: Angle) -> Angle {
+        Angle(self.0.wrapping_add(other.0))
+    }
+}
+
+impl AddAssign for Angle {
+    fn add_assign(&mut self, other: Angle) {
+        *self = *self + other;
+    }
+}
+
+impl Sub for Angle {
+    type Output = Angle;
+    fn sub(self, other: Angle) -> Angle {
+        Angle(self.0.wrapping_sub(other.0))
+    }
+}
+
+impl SubAssign for Angle {
+    fn sub_assign(&mut self, other: Angle) {
+        *self = *self - other;
+    }
+}
+
+impl Neg for Angle {
+    type Output = Angle;
+    fn neg(self) -> Angle {
+        Angle(self.0.wrapping_neg())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn of_vector(x: i32, y: i32) -> Angle {
+        Angle::of_vector(Fixed::from_int(x), Fixed::from_int(y))
+    }
+
+    #[test]
+    fn of_vector_octants() {
+        let cases = [
+            ((2, 1), 26.565),
+            ((1, 2), 63.435),
+            ((-1, 2), 116.565),
+            ((-2, 1), 153.435),
+            ((-2, -1), 206.565),
+            ((-1, -2), 243.435),
+            ((1, -2), 296.565),
+            ((2, -1), 333.435),
+        ];
+        for ((x, y), degrees) in cases {
+            let angle = of_vector(x, y).to_degrees();
+            assert!((angle - degrees).abs() < 0.02, "({x}, {y}): {angle}");
+            // The length of the vector does not matter.
+            assert_eq!(of_vector(x * 1000, y * 1000), of_vector(x, y), "({x}, {y})");
+        }
+    }
+
+    #[test]
+    fn of_vector_axes_and_diagonals() {
+        assert_eq!(of_vector(0, 0), Angle(0));
+        assert_eq!(of_vector(1, 0), Angle(0));
+        assert_eq!(of_vector(1, 1), Angle(ANG45 - 1));
+        assert_eq!(of_vector(0, 1), Angle(ANG90 - 1));
+        assert_eq!(of_vector(-1, 1), Angle(ANG90 + ANG45));
+        assert_eq!(of_vector(-1, 0), Angle(ANG180 - 1));
+        assert_eq!(of_vector(-1, -1), Angle(ANG180 + ANG45 - 1));
+        assert_eq!(of_vector(0, -1), Angle(ANG270));
+        assert_eq!(of_vector(1, -1), Angle(ANG270 + ANG45));
+    }
+
+    #[test]
+    fn sin_cos_tan() {
+        assert_eq!(Angle(0).sin(), Fixed(25));
+        assert_eq!(Angle(0).cos(), Fixed(65535));
+        assert_eq!(Angle(ANG90).sin(), Fixed(65535));
+        assert_eq!(Angle(ANG180).cos(), Fixed(-65535));
+        assert_eq!(Angle(ANG45).tan(), Fixed(FINE_TANGENT[FINE_ANGLES / 4 + FINE_ANGLES / 8]));
+        assert!((Angle(ANG45).tan().to_f32() - 1.0).abs() < 0.001);
+        assert!((Angle(0u32.wrapping_sub(ANG45)).tan().to_f32() + 1.0).abs() < 0.001);
+    }
+}
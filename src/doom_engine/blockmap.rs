@@ -0,0 +1,210 @@
+/// Size in map units of the square cells of the blockmap grid.
+pub const BLOCK_SIZE: i32 = 128;
+
+pub struct Blockmap {
+    pub origin_x: i16,
+    pub origin_y: i16,
+    pub columns: usize,
+    pub rows: usize,
+    /// Linedef ids per block, row-major starting at the bottom-left corner.
+    pub blocks: Vec<Vec<u16>>,
+}
+
+impl Blockmap {
+    /// A blockmap without blocks, for maps shipped with an empty BLOCKMAP lump.
+    pub fn empty() -> Self {
+        Blockmap {
+            origin_x: 0,
+            origin_y: 0,
+            columns: 0,
+            rows: 0,
+            blocks: Vec::new(),
+        }
+    }
+
+    /// Column and row of the block containing the map point, if it is inside the grid.
+    pub fn block_at(&self, x: i32, y: i32) -> Option<(usize, usize)> {
+        let col = (x - self.origin_x as i32).div_euclid(BLOCK_SIZE);
+        let row = (y - self.origin_y as i32).div_euclid(BLOCK_SIZE);
+        self.in_grid(col, row)
+    }
+
+    pub fn linedefs_in_block(&self, col: usize, row: usize) -> &[u16] {
+        if col >= self.columns || row >= self.rows {
+            return &[];
+        }
+        &self.blocks[row * self.columns + col]
+    }
+
+    /// Linedefs in the block containing the map point.
+    pub fn linedefs_at(&self, x: i32, y: i32) -> &[u16] {
+        match self.block_at(x, y) {
+            Some((col, row)) => self.linedefs_in_block(col, row),
+            None => &[],
+        }
+    }
+
+    /// Blocks crossed by the segment from `(x0, y0)` to `(x1, y1)`, in order from
+    /// the start point. Blocks outside the grid are skipped.
+    pub fn blocks_on_line(&self, x0: i32, y0: i32, x1: i32, y1: i32) -> BlockLineIter<'_> {
+        let fx0 = (x0 - self.origin_x as i32) as f64 / BLOCK_SIZE as f64;
+        let fy0 = (y0 - self.origin_y as i32) as f64 / BLOCK_SIZE as f64;
+        let fx1 = (x1 - self.origin_x as i32) as f64 / BLOCK_SIZE as f64;
+        let fy1 = (y1 - self.origin_y as i32) as f64 / BLOCK_SIZE as f64;
+
+        let col = fx0.floor() as i32;
+        let row = fy0.floor() as i32;
+        let end_col = fx1.floor() as i32;
+        let end_row = fy1.floor() as i32;
+
+        let (step_col, t_delta_x, t_max_x) = axis_step(fx0, fx1);
+        let (step_row, t_delta_y, t_max_y) = axis_step(fy0, fy1);
+        let steps = (end_col - col).unsigned_abs() + (end_row - row).unsigned_abs();
+
+        BlockLineIter {
+            blockmap: self,
+            col,
+            row,
+            step_col,
+            step_row,
+            t_max_x,
+            t_max_y,
+            t_delta_x,
+            t_delta_y,
+            remaining: steps as usize + 1,
+        }
+    }
+
+    fn in_grid(&self, col: i32, row: i32) -> Option<(usize, usize)> {
+        if col < 0 || row < 0 || col as usize >= self.columns || row as usize >= self.rows {
+            None
+        } else {
+            Some((col as usize, row as usize))
+        }
+    }
+}
+
+/// Direction, parametric distance between grid lines and parametric distance to the
+/// first grid line along one axis of a segment going from `from` to `to`.
+fn axis_step(from: f64, to: f64) -> (i32, f64, f64) {
+    let d = to - from;
+    if d > 0.0 {
+        (1, 1.0 / d, (from.floor() + 1.0 - from) / d)
+    } else if d < 0.0 {
+        (-1, -1.0 / d, (from - from.floor()) / -d)
+    } else {
+        (0, f64::INFINITY, f64::INFINITY)
+    }
+}
+
+pub struct BlockLineIter<'a> {
+    blockmap: &'a Blockmap,
+    col: i32,
+    row: i32,
+    step_col: i32,
+    step_row: i32,
+    t_max_x: f64,
+    t_max_y: f64,
+    t_delta_x: f64,
+    t_delta_y: f64,
+    remaining: usize,
+}
+
+impl<'a> Iterator for BlockLineIter<'a> {
+    type Item = (usize, usize);
+
+    fn next(&mut self) -> Option<(usize, usize)> {
+        while self.remaining > 0 {
+            let current = self.blockmap.in_grid(self.col, self.row);
+            self.remaining -= 1;
+            if self.t_max_x < self.t_max_y {
+                self.col += self.step_col;
+                self.t_max_x += self.t_delta_x;
+            } else {
+                self.row += self.step_row;
+                self.t_max_y += self.t_delta_y;
+            }
+            if current.is_some() {
+                return current;
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 3x2 grid at (-128, 0) whose block `i` holds linedef `i`.
+    fn blockmap() -> Blockmap {
+        Blockmap {
+            origin_x: -128,
+            origin_y: 0,
+            columns: 3,
+            rows: 2,
+            blocks: (0..6).map(|i| vec![i]).collect(),
+        }
+    }
+
+    fn blocks_on_line(from: (i32, i32), to: (i32, i32)) -> Vec<(usize, usize)> {
+        blockmap().blocks_on_line(from.0, from.1, to.0, to.1).collect()
+    }
+
+    #[test]
+    fn block_at_a_point() {
+        let blockmap = blockmap();
+        assert_eq!(blockmap.block_at(-128, 0), Some((0, 0)));
+        assert_eq!(blockmap.block_at(-1, 127), Some((0, 0)));
+        assert_eq!(blockmap.block_at(0, 128), Some((1, 1)));
+        assert_eq!(blockmap.block_at(255, 255), Some((2, 1)));
+        assert_eq!(blockmap.block_at(-129, 0), None);
+        assert_eq!(blockmap.block_at(0, -1), None);
+        assert_eq!(blockmap.block_at(256, 0), None);
+        assert_eq!(blockmap.linedefs_at(10, 200), &[4]);
+        assert_eq!(blockmap.linedefs_at(10, 300), &[] as &[u16]);
+        assert_eq!(blockmap.linedefs_in_block(3, 0), &[] as &[u16]);
+    }
+
+    #[test]
+    fn horizontal_line() {
+        assert_eq!(blocks_on_line((-100, 10), (200, 10)), [(0, 0), (1, 0), (2, 0)]);
+        assert_eq!(blocks_on_line((200, 10), (-100, 10)), [(2, 0), (1, 0), (0, 0)]);
+    }
+
+    #[test]
+    fn vertical_line() {
+        assert_eq!(blocks_on_line((10, 10), (10, 250)), [(1, 0), (1, 1)]);
+        assert_eq!(blocks_on_line((10, 250), (10, 10)), [(1, 1), (1, 0)]);
+    }
+
+    #[test]
+    fn line_along_grid_lines() {
+        // Points on a grid line belong to the block above or to the right.
+        assert_eq!(blocks_on_line((-128, 128), (255, 128)), [(0, 1), (1, 1), (2, 1)]);
+        assert_eq!(blocks_on_line((0, 0), (0, 255)), [(1, 0), (1, 1)]);
+        // Through a grid corner, the block above it comes before the one to
+        // its right; the end point lies just outside the grid.
+        assert_eq!(blocks_on_line((-128, 0), (128, 256)), [(0, 0), (0, 1), (1, 1)]);
+    }
+
+    #[test]
+    fn diagonal_line() {
+        assert_eq!(blocks_on_line((-100, 10), (200, 200)), [(0, 0), (1, 0), (1, 1), (2, 1)]);
+        assert_eq!(blocks_on_line((200, 200), (-100, 10)), [(2, 1), (1, 1), (1, 0), (0, 0)]);
+    }
+
+    #[test]
+    fn line_leaving_the_grid() {
+        assert_eq!(blocks_on_line((200, 10), (600, 10)), [(2, 0)]);
+        assert_eq!(blocks_on_line((-300, 300), (-100, 100)), [(0, 0)]);
+        assert_eq!(blocks_on_line((-300, 500), (600, 500)), []);
+        // Crossing the whole grid from outside.
+        assert_eq!(blocks_on_line((-500, 64), (500, 64)), [(0, 0), (1, 0), (2, 0)]);
+    }
+
+    #[test]
+    fn single_point() {
+        assert_eq!(blocks_on_line((10, 10), (10, 10)), [(1, 0)]);
+    }
+}
@@ -1,39 +1,180 @@
 use super::Player;
+use super::bsp_debug::BspStep;
 use super::data_types::*;
+use super::fixed::Fixed;
+use super::view_renderer::ViewRenderer;
 
-const SUB_SECTOR_IDENTIFIER:u16 = 0x8000;
+pub const SUB_SECTOR_IDENTIFIER:u16 = 0x8000;
+#[allow(clippy::upper_case_acronyms)]
 pub struct BSP {
     pub root_node_id: usize,
 }
 
 impl BSP {
+    pub fn new(nodes: &[Node]) -> Self {
+        // A map with a single subsector has no nodes.
+        let root_node_id = if nodes.is_empty() {
+            SUB_SECTOR_IDENTIFIER as usize
+        } else {
+            nodes.len() - 1
+        };
+        BSP { root_node_id }
+    }
+
     pub fn is_on_back_side(p:&Player, n:&Node)-> bool {
-        let dx = p.pos.0 - n.x_partition;
-        let dy = p.pos.1 - n.y_partition;
-        dx * n.dy_partition -dy*n.dx_partition <= 0 
+        BSP::point_on_back_side(p.pos.0, p.pos.1, n)
     }
 
-    pub fn render_sub_sector(sub_sector_id:u16){
-        println!("rendo {sub_sector_id}");
+    /// Whether the map point is behind the partition line of the node; see
+    /// `point_on_line_back_side`.
+    pub fn point_on_back_side(x: Fixed, y: Fixed, n: &Node) -> bool {
+        BSP::point_on_line_back_side(
+            x,
+            y,
+            (n.x_partition.into(), n.y_partition.into()),
+            (n.dx_partition.into(), n.dy_partition.into()),
+        )
     }
 
-    pub fn render_bsp_node(player: &Player, nodes: &Vec<Node>, node_id:u16) {
+    /// Whether the map point is behind the line from `origin` going `delta` map
+    /// units, decided like `R_PointOnSide`: the back is on the left of the
+    /// line, and points on the line are behind.
+    pub fn point_on_line_back_side(x: Fixed, y: Fixed, origin: (i32, i32), delta: (i32, i32)) -> bool {
+        let (line_x, line_y) = (Fixed::from_int(origin.0), Fixed::from_int(origin.1));
+        let (line_dx, line_dy) = delta;
+        if line_dx == 0 {
+            return if x <= line_x { line_dy > 0 } else { line_dy < 0 };
+        }
+        if line_dy == 0 {
+            return if y <= line_y { line_dx < 0 } else { line_dx > 0 };
+        }
+
+        let dx = x - line_x;
+        let dy = y - line_y;
+        // The signs decide when the two products have different signs.
+        if (line_dy ^ line_dx ^ dx.0 ^ dy.0) < 0 {
+            return (line_dy ^ dx.0) < 0;
+        }
+        // The deltas are whole map units, so their integer parts are
+        // multiplied with the point's fixed offsets.
+        let left = Fixed(line_dy) * dx;
+        let right = dy * Fixed(line_dx);
+        right >= left
+    }
+
+    /// Subsector containing the map point.
+    pub fn point_in_sub_sector(&self, nodes: &[Node], x: Fixed, y: Fixed) -> u16 {
+        let mut node_id = self.root_node_id as u16;
+        while node_id < SUB_SECTOR_IDENTIFIER {
+            let node = &nodes[node_id as usize];
+            node_id = if BSP::point_on_back_side(x, y, node) {
+                node.back_child_id
+            } else {
+                node.front_child_id
+            };
+        }
+        node_id - SUB_SECTOR_IDENTIFIER
+    }
+
+    /// Draws the view front to back.
+    pub fn render(&self, view: &mut ViewRenderer) {
+        BSP::render_bsp_node(view, self.root_node_id as u16);
+    }
+
+    pub fn render_sub_sector(view: &mut ViewRenderer, sub_sector_id:u16){
+        view.render_sub_sector(sub_sector_id);
+    }
+
+    pub fn render_bsp_node(view: &mut ViewRenderer, node_id:u16) {
+        // Nothing behind a fully covered screen can be seen.
+        if view.is_screen_full() {
+            return;
+        }
+
         if node_id >= SUB_SECTOR_IDENTIFIER {
             let sub_sector_id = node_id - SUB_SECTOR_IDENTIFIER;
-            BSP::render_sub_sector(sub_sector_id);
+            BSP::render_sub_sector(view, sub_sector_id);
             return;
         }
 
-        let node = &(*nodes)[node_id as usize];
+        let wad_data = view.wad_data;
+        let node = &wad_data.nodes[node_id as usize];
+        view.trace(BspStep::Node(node_id));
 
-        let is_on_back = BSP::is_on_back_side(player, &node);
+        // The far side is only visited if its bounding box may be visible.
+        let is_on_back = BSP::is_on_back_side(view.player, node);
         if is_on_back {
-            BSP::render_bsp_node(player, nodes, node.back_child_id);
-            BSP::render_bsp_node(player, nodes, node.front_child_id);
+            BSP::render_bsp_node(view, node.back_child_id);
+            if view.check_bbox(&node.bbox_front) {
+                BSP::render_bsp_node(view, node.front_child_id);
+            } else {
+                view.trace(BspStep::Culled { node_id, back: false });
+            }
         }
         else {
-            BSP::render_bsp_node(player, nodes, node.front_child_id);
-            BSP::render_bsp_node(player, nodes, node.back_child_id);
+            BSP::render_bsp_node(view, node.front_child_id);
+            if view.check_bbox(&node.bbox_back) {
+                BSP::render_bsp_node(view, node.back_child_id);
+            } else {
+                view.trace(BspStep::Culled { node_id, back: true });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(x: i16, y: i16, dx: i16, dy: i16) -> Node {
+        let bbox = || BBox { top: 0, bottom: 0, left: 0, right: 0 };
+        Node {
+            x_partition: x,
+            y_partition: y,
+            dx_partition: dx,
+            dy_partition: dy,
+            bbox_front: bbox(),
+            bbox_back: bbox(),
+            front_child_id: 0,
+            back_child_id: 0,
+        }
+    }
+
+    fn on_back(x: i32, y: i32, n: &Node) -> bool {
+        BSP::point_on_back_side(Fixed::from_int(x), Fixed::from_int(y), n)
+    }
+
+    #[test]
+    fn axis_aligned_partitions() {
+        // Going north along x = 0: east is in front, west behind.
+        let north = node(0, 0, 0, 64);
+        assert!(!on_back(1, 5, &north));
+        assert!(on_back(-1, 5, &north));
+        // Going east along y = 0: south is in front, north behind.
+        let east = node(0, 0, 64, 0);
+        assert!(!on_back(5, -1, &east));
+        assert!(on_back(5, 1, &east));
+        // Reversed partitions swap the sides.
+        assert!(!on_back(-1, 5, &node(0, 0, 0, -64)));
+        assert!(!on_back(5, 1, &node(0, 0, -64, 0)));
+    }
+
+    #[test]
+    fn diagonal_partition() {
+        let n = node(0, 0, 64, 64);
+        assert!(!on_back(10, 0, &n));
+        assert!(on_back(0, 10, &n));
+        assert!(on_back(10, 10, &n), "points on the line are behind");
+    }
+
+    #[test]
+    fn big_coordinates() {
+        // The products of the offsets and deltas do not fit in 32 bits.
+        let n = node(0, 0, 32767, 32766);
+        let points = [(32767, 32767), (32767, 32766), (32767, 32765), (-32767, -32765), (-32767, -32767), (32767, -32767), (-32767, 32767)];
+        for (x, y) in points {
+            let back = y as i64 * 32767 >= 32766 * x as i64;
+            assert_eq!(on_back(x, y, &n), back, "({x}, {y})");
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,142 @@
+use super::data_types::BBox;
+use super::frame_buffer::FrameBuffer;
+use super::map_renderer::{clip_line, MapView, GRAYS, GREENS, REDS, WHITE};
+use super::wad_data::WadData;
+
+// Palette indices of the overlay colors, besides the automap ones.
+const BLUES: u8 = 200;
+const ORANGES: u8 = 216;
+const PURPLES: u8 = 250;
+const LIGHT_YELLOWS: u8 = 160;
+
+const PARTITION_COLOR: u8 = WHITE;
+const FRONT_BOX_COLOR: u8 = GREENS;
+const BACK_BOX_COLOR: u8 = BLUES;
+const CULLED_BOX_COLOR: u8 = REDS;
+/// Segs of visited subsectors that drew nothing.
+const HIDDEN_SEG_COLOR: u8 = GRAYS + 8;
+/// Colors of the subsectors, repeated in visit order.
+const SUB_SECTOR_COLORS: [u8; 5] = [GREENS, BLUES, ORANGES, PURPLES, LIGHT_YELLOWS];
+
+/// One step of the traversal of the BSP tree while rendering the view.
+pub enum BspStep {
+    /// A node is entered; its side facing the viewer is visited first.
+    Node(u16),
+    /// The child of a node on the given side was skipped, as its bounding box
+    /// cannot be seen.
+    Culled { node_id: u16, back: bool },
+    /// A subsector is drawn; `drawn_segs` are the ids of its segs that put
+    /// columns on the screen.
+    SubSector { sub_sector_id: u16, drawn_segs: Vec<u16> },
+}
+
+/// An overlay of the automap replaying the BSP traversal of the last view
+/// from the player's position, one step at a time.
+#[derive(Default)]
+pub struct BspDebug {
+    pub active: bool,
+    trace: Vec<BspStep>,
+    /// Number of steps shown.
+    step: usize,
+}
+
+impl BspDebug {
+    /// Shows or hides the overlay, starting again at the first step.
+    pub fn toggle(&mut self) {
+        self.active = !self.active;
+        self.step = 1;
+    }
+
+    pub fn step_forward(&mut self) {
+        self.step = (self.step + 1).min(self.trace.len());
+    }
+
+    pub fn step_back(&mut self) {
+        self.step = self.step.saturating_sub(1);
+    }
+
+    /// Replaces the traversal, e.g. after the player moved, keeping the step.
+    pub fn set_trace(&mut self, trace: Vec<BspStep>) {
+        self.trace = trace;
+        self.step = self.step.min(self.trace.len());
+    }
+
+    /// Draws the subsectors visited so far, then the current step on top.
+    pub fn draw(&self, screen: &mut FrameBuffer, wad_data: &WadData, view: &MapView) {
+        let shown = &self.trace[..self.step];
+        let mut sub_sectors = 0;
+        for step in shown {
+            if let BspStep::SubSector { sub_sector_id, drawn_segs } = step {
+                let color = SUB_SECTOR_COLORS[sub_sectors % SUB_SECTOR_COLORS.len()];
+                draw_sub_sector(screen, wad_data, view, *sub_sector_id, drawn_segs, color);
+                sub_sectors += 1;
+            }
+        }
+
+        match shown.last() {
+            Some(BspStep::Node(node_id)) => {
+                let node = &wad_data.nodes[*node_id as usize];
+                draw_bbox(screen, view, &node.bbox_front, FRONT_BOX_COLOR);
+                draw_bbox(screen, view, &node.bbox_back, BACK_BOX_COLOR);
+                draw_partition(screen, wad_data, view, *node_id);
+            }
+            Some(BspStep::Culled { node_id, back }) => {
+                let node = &wad_data.nodes[*node_id as usize];
+                let bbox = if *back { &node.bbox_back } else { &node.bbox_front };
+                draw_bbox(screen, view, bbox, CULLED_BOX_COLOR);
+                draw_partition(screen, wad_data, view, *node_id);
+            }
+            Some(BspStep::SubSector { sub_sector_id, drawn_segs }) => {
+                draw_sub_sector(screen, wad_data, view, *sub_sector_id, drawn_segs, WHITE);
+            }
+            None => {}
+        }
+    }
+}
+
+/// Segs of a subsector, in `color` if they were drawn.
+fn draw_sub_sector(
+    screen: &mut FrameBuffer,
+    wad_data: &WadData,
+    view: &MapView,
+    sub_sector_id: u16,
+    drawn_segs: &[u16],
+    color: u8,
+) {
+    let sub_sector = &wad_data.sub_sectors[sub_sector_id as usize];
+    let first = sub_sector.first_seg_id;
+    for seg_id in first..first + sub_sector.seg_count {
+        let seg = &wad_data.segments[seg_id as usize];
+        let v1 = &wad_data.vertexes[seg.start_vertex_id as usize];
+        let v2 = &wad_data.vertexes[seg.end_vertex_id as usize];
+        let color = if drawn_segs.contains(&seg_id) { color } else { HIDDEN_SEG_COLOR };
+        view.line(screen, (v1.x as f32, v1.y as f32), (v2.x as f32, v2.y as f32), color);
+    }
+}
+
+/// The partition line of a node, across the boxes of both children.
+fn draw_partition(screen: &mut FrameBuffer, wad_data: &WadData, view: &MapView, node_id: u16) {
+    let node = &wad_data.nodes[node_id as usize];
+    let (front, back) = (&node.bbox_front, &node.bbox_back);
+    let min = (front.left.min(back.left) as f32, front.bottom.min(back.bottom) as f32);
+    let max = (front.right.max(back.right) as f32, front.top.max(back.top) as f32);
+
+    // Stretch the partition past the boxes before clipping it to them.
+    let (x, y) = (node.x_partition as f32, node.y_partition as f32);
+    let (dx, dy) = (node.dx_partition as f32, node.dy_partition as f32);
+    let stretch = (max.0 - min.0 + max.1 - min.1) / dx.hypot(dy).max(1.0);
+    let a = (x - dx * stretch, y - dy * stretch);
+    let b = (x + dx * (1.0 + stretch), y + dy * (1.0 + stretch));
+    if let Some((a, b)) = clip_line(a, b, min, max) {
+        view.line(screen, a, b, PARTITION_COLOR);
+    }
+}
+
+fn draw_bbox(screen: &mut FrameBuffer, view: &MapView, bbox: &BBox, color: u8) {
+    let (left, right) = (bbox.left as f32, bbox.right as f32);
+    let (bottom, top) = (bbox.bottom as f32, bbox.top as f32);
+    view.line(screen, (left, top), (right, top), color);
+    view.line(screen, (right, top), (right, bottom), color);
+    view.line(screen, (right, bottom), (left, bottom), color);
+    view.line(screen, (left, bottom), (left, top), color);
+}
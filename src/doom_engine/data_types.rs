@@ -1,18 +1,75 @@
+use super::game::{GameMode, Skill};
+
 pub struct Vertex {
     pub x: i16,
     pub y: i16,
 }
 
+/// Sidedef id stored in a linedef that has no sidedef on that side.
+pub const NO_SIDEDEF: u16 = 0xFFFF;
+
+// Linedef flags. The blocking and sound flags are left out until movement
+// and sound propagation use them.
+pub const ML_TWOSIDED: u16 = 4;
+pub const ML_DONTPEGTOP: u16 = 8;
+pub const ML_DONTPEGBOTTOM: u16 = 16;
+pub const ML_SECRET: u16 = 32;
+pub const ML_DONTDRAW: u16 = 128;
+pub const ML_MAPPED: u16 = 256;
+
 pub struct Linedef {
     pub start_vertex_id: u16,
     pub end_vertex_id: u16,
     pub flags: u16,
     pub line_type: u16,
+    /// Tag of the sectors the line acts on.
     pub sector_tag: u16,
     pub front_sidedef_id: u16,
     pub back_sidedef_id: u16,
 }
 
+impl Linedef {
+    /// Whether the line separates two sectors. The renderer treats such lines
+    /// as portals; the flag is what the original engine checks.
+    pub fn is_two_sided(&self) -> bool {
+        self.flags & ML_TWOSIDED != 0 && self.back_sidedef_id != NO_SIDEDEF
+    }
+}
+
+pub struct Sidedef {
+    pub x_offset: i16,
+    pub y_offset: i16,
+    pub upper_texture: [u8; 8],
+    pub lower_texture: [u8; 8],
+    pub middle_texture: [u8; 8],
+    pub sector_id: u16,
+}
+
+pub struct Sector {
+    pub floor_height: i16,
+    pub ceiling_height: i16,
+    pub floor_texture: [u8; 8],
+    pub ceiling_texture: [u8; 8],
+    pub light_level: i16,
+    /// Effect of the sector, e.g. blinking lights or damage.
+    pub special: u16,
+    /// Matched by the `sector_tag` of the linedefs acting on the sector.
+    pub tag: u16,
+}
+
+// Thing flags.
+pub const MTF_EASY: u16 = 1;
+pub const MTF_NORMAL: u16 = 2;
+pub const MTF_HARD: u16 = 4;
+/// Only present in cooperative and deathmatch games.
+pub const MTF_NOTSINGLE: u16 = 16;
+
+/// Thing types of the starts of players 1 to 4, and of deathmatch starts.
+pub const PLAYER_1_START: u16 = 1;
+pub const PLAYER_4_START: u16 = 4;
+pub const DEATHMATCH_START: u16 = 11;
+
+#[derive(Clone)]
 pub struct Thing {
     pub pos: (i16,i16),
     pub angle: i16,
@@ -20,6 +77,24 @@ pub struct Thing {
     pub flags: u16,
 }
 
+impl Thing {
+    pub fn is_start(&self) -> bool {
+        (PLAYER_1_START..=PLAYER_4_START).contains(&self.ttype) || self.ttype == DEATHMATCH_START
+    }
+
+    /// Whether the thing is placed in a game of this skill and mode. Starts
+    /// only mark where players spawn.
+    pub fn spawns_in(&self, skill: Skill, mode: GameMode) -> bool {
+        if self.is_start() {
+            return false;
+        }
+        if mode == GameMode::SinglePlayer && self.flags & MTF_NOTSINGLE != 0 {
+            return false;
+        }
+        self.flags & skill.thing_flag() != 0
+    }
+}
+
 pub struct Seg {
     pub start_vertex_id: u16,
     pub end_vertex_id: u16,
@@ -52,3 +127,87 @@ pub struct Node {
     pub left: i16,
     pub right: i16,
 }
+
+/// A run of opaque pixels in a picture column.
+pub struct Post {
+    pub top_delta: usize,
+    pub pixels: Vec<u8>,
+}
+
+/// A picture lump (wall patch, sprite...) decoded to its columns.
+pub struct Picture {
+    pub width: usize,
+    pub height: usize,
+    pub left_offset: i16,
+    pub top_offset: i16,
+    pub columns: Vec<Vec<Post>>,
+}
+
+/// A patch placed in a composite texture.
+pub struct PatchRef {
+    pub origin_x: i16,
+    pub origin_y: i16,
+    /// Index into PNAMES.
+    pub patch_id: u16,
+}
+
+/// A composite texture definition from TEXTURE1 or TEXTURE2.
+pub struct TextureDef {
+    pub name: [u8; 8],
+    pub width: u16,
+    pub height: u16,
+    pub patches: Vec<PatchRef>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn thing(ttype: u16, flags: u16) -> Thing {
+        Thing {
+            pos: (0, 0),
+            angle: 0,
+            ttype,
+            flags,
+        }
+    }
+
+    const SKILLS: [Skill; 5] = [Skill::Baby, Skill::Easy, Skill::Medium, Skill::Hard, Skill::Nightmare];
+
+    #[test]
+    fn skill_bits() {
+        let cases = [
+            (MTF_EASY, [true, true, false, false, false]),
+            (MTF_NORMAL, [false, false, true, false, false]),
+            (MTF_HARD, [false, false, false, true, true]),
+            (MTF_EASY | MTF_HARD, [true, true, false, true, true]),
+            (0, [false; 5]),
+        ];
+        for (flags, spawns) in cases {
+            for (skill, spawns) in SKILLS.into_iter().zip(spawns) {
+                assert_eq!(thing(2035, flags).spawns_in(skill, GameMode::SinglePlayer), spawns, "{flags} {skill:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn multiplayer_only_things() {
+        let shotgun = thing(2001, MTF_EASY | MTF_NORMAL | MTF_HARD | MTF_NOTSINGLE);
+        assert!(!shotgun.spawns_in(Skill::Medium, GameMode::SinglePlayer));
+        assert!(shotgun.spawns_in(Skill::Medium, GameMode::Cooperative));
+        assert!(shotgun.spawns_in(Skill::Medium, GameMode::Deathmatch));
+        // The skill bits still apply.
+        let shotgun = thing(2001, MTF_HARD | MTF_NOTSINGLE);
+        assert!(!shotgun.spawns_in(Skill::Medium, GameMode::Cooperative));
+    }
+
+    #[test]
+    fn starts_are_not_spawned() {
+        for ttype in [1, 2, 3, 4, 11] {
+            for mode in [GameMode::SinglePlayer, GameMode::Cooperative, GameMode::Deathmatch] {
+                assert!(!thing(ttype, 7).spawns_in(Skill::Medium, mode), "{ttype} {mode:?}");
+            }
+        }
+        assert!(thing(5, 7).spawns_in(Skill::Medium, GameMode::SinglePlayer));
+    }
+}
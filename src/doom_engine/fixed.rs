@@ -0,0 +1,136 @@
+use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign};
+
+pub const FRAC_BITS: u32 = 16;
+pub const FRAC_UNIT: i32 = 1 << FRAC_BITS;
+
+/// A 16.16 fixed point number, the number type of the original engine for
+/// positions, distances and scales. Sums wrap around like the 32-bit
+/// integers of the original code instead of overflowing.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Default)]
+pub struct Fixed(pub i32);
+
+impl Fixed {
+    pub const ZERO: Fixed = Fixed(0);
+    pub const MAX: Fixed = Fixed(i32::MAX);
+    pub const MIN: Fixed = Fixed(i32::MIN);
+
+    pub const fn from_int(n: i32) -> Fixed {
+        Fixed(n << FRAC_BITS)
+    }
+
+    #[cfg(test)]
+    pub fn from_f32(f: f32) -> Fixed {
+        Fixed((f * FRAC_UNIT as f32) as i32)
+    }
+
+    pub fn to_f32(self) -> f32 {
+        self.0 as f32 / FRAC_UNIT as f32
+    }
+
+    pub fn abs(self) -> Fixed {
+        Fixed(self.0.wrapping_abs())
+    }
+}
+
+impl From<i16> for Fixed {
+    fn from(n: i16) -> Fixed {
+        Fixed::from_int(n as i32)
+    }
+}
+
+impl Add for Fixed {
+    type Output = Fixed;
+    fn add(self, other: Fixed) -> Fixed {
+        Fixed(self.0.wrapping_add(other.0))
+    }
+}
+
+impl AddAssign for Fixed {
+    fn add_assign(&mut self, other: Fixed) {
+        *self = *self + other;
+    }
+}
+
+impl Sub for Fixed {
+    type Output = Fixed;
+    fn sub(self, other: Fixed) -> Fixed {
+        Fixed(self.0.wrapping_sub(other.0))
+    }
+}
+
+impl SubAssign for Fixed {
+    fn sub_assign(&mut self, other: Fixed) {
+        *self = *self - other;
+    }
+}
+
+impl Neg for Fixed {
+    type Output = Fixed;
+    fn neg(self) -> Fixed {
+        Fixed(self.0.wrapping_neg())
+    }
+}
+
+/// `FixedMul`: the product is taken on 64 bits, so it only wraps if the
+/// result itself does not fit.
+impl Mul for Fixed {
+    type Output = Fixed;
+    fn mul(self, other: Fixed) -> Fixed {
+        Fixed(((self.0 as i64 * other.0 as i64) >> FRAC_BITS) as i32)
+    }
+}
+
+/// `FixedDiv`: quotients too large for 16.16 saturate to `MAX` or `MIN`,
+/// which also covers division by zero.
+impl Div for Fixed {
+    type Output = Fixed;
+    fn div(self, other: Fixed) -> Fixed {
+        if self.0.unsigned_abs() >> 14 >= other.0.unsigned_abs() {
+            return if (self.0 ^ other.0) < 0 { Fixed::MIN } else { Fixed::MAX };
+        }
+        Fixed((((self.0 as i64) << FRAC_BITS) / other.0 as i64) as i32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mul() {
+        assert_eq!(Fixed::from_int(3) * Fixed::from_f32(0.5), Fixed::from_f32(1.5));
+        assert_eq!(Fixed::from_int(-3) * Fixed::from_int(2), Fixed::from_int(-6));
+        assert_eq!(Fixed::from_int(-3) * Fixed::from_int(-2), Fixed::from_int(6));
+        // The shift rounds towards negative infinity, as in the original.
+        assert_eq!(Fixed(-1) * Fixed(1), Fixed(-1));
+        // Only products that do not fit wrap.
+        assert_eq!(Fixed::from_int(30000) * Fixed(FRAC_UNIT / 2), Fixed::from_int(15000));
+        assert_eq!(Fixed::from_int(300) * Fixed::from_int(300), Fixed((90000i64 << FRAC_BITS) as i32));
+    }
+
+    #[test]
+    fn div() {
+        assert_eq!(Fixed::from_int(7) / Fixed::from_int(2), Fixed::from_f32(3.5));
+        assert_eq!(Fixed::from_int(-7) / Fixed::from_int(2), Fixed::from_f32(-3.5));
+        assert_eq!(Fixed::from_int(7) / Fixed::from_int(-2), Fixed::from_f32(-3.5));
+        assert_eq!(Fixed::from_int(-7) / Fixed::from_int(-2), Fixed::from_f32(3.5));
+    }
+
+    #[test]
+    fn div_saturates() {
+        assert_eq!(Fixed::from_int(20000) / Fixed::from_int(1), Fixed::MAX);
+        assert_eq!(Fixed::from_int(-20000) / Fixed::from_int(1), Fixed::MIN);
+        assert_eq!(Fixed::from_int(20000) / Fixed::from_int(-1), Fixed::MIN);
+        assert_eq!(Fixed::from_int(1) / Fixed(1), Fixed::MAX);
+        // Quotients of 2^14 and more saturate even when they would fit.
+        assert_eq!(Fixed::from_int(16384) / Fixed::from_int(1), Fixed::MAX);
+        assert_eq!(Fixed::from_int(16383) / Fixed::from_int(1), Fixed::from_int(16383));
+    }
+
+    #[test]
+    fn div_by_zero() {
+        assert_eq!(Fixed::from_int(1) / Fixed::ZERO, Fixed::MAX);
+        assert_eq!(Fixed::from_int(-1) / Fixed::ZERO, Fixed::MIN);
+        assert_eq!(Fixed::ZERO / Fixed::ZERO, Fixed::MAX);
+    }
+}
@@ -0,0 +1,98 @@
+use super::palette::Palette;
+
+/// Layout of the pixels of a frame buffer.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PixelFormat {
+    /// One palette index per pixel.
+    Indexed8,
+    Rgb8,
+    Rgba8,
+}
+
+impl PixelFormat {
+    pub fn bytes_per_pixel(&self) -> usize {
+        match self {
+            PixelFormat::Indexed8 => 1,
+            PixelFormat::Rgb8 => 3,
+            PixelFormat::Rgba8 => 4,
+        }
+    }
+}
+
+/// A render target borrowed from a window surface, an image or a test:
+/// `height` rows of `width` pixels, rows starting `pitch` bytes apart.
+pub struct FrameBuffer<'a> {
+    pub pixels: &'a mut [u8],
+    pub width: usize,
+    pub height: usize,
+    pub pitch: usize,
+    pub format: PixelFormat,
+}
+
+impl<'a> FrameBuffer<'a> {
+    /// A buffer with tightly packed rows.
+    pub fn new(pixels: &'a mut [u8], width: usize, height: usize, format: PixelFormat) -> Self {
+        FrameBuffer::with_pitch(pixels, width, height, width * format.bytes_per_pixel(), format)
+    }
+
+    /// A buffer whose rows may be padded, e.g. a locked texture.
+    ///
+    /// Panics if `pixels` is too small for the given size.
+    pub fn with_pitch(pixels: &'a mut [u8], width: usize, height: usize, pitch: usize, format: PixelFormat) -> Self {
+        assert!(pitch >= width * format.bytes_per_pixel(), "pitch is shorter than a row");
+        assert!(
+            height == 0 || pixels.len() >= (height - 1) * pitch + width * format.bytes_per_pixel(),
+            "frame buffer is too small"
+        );
+        FrameBuffer {
+            pixels,
+            width,
+            height,
+            pitch,
+            format,
+        }
+    }
+
+    /// Byte offset of a pixel.
+    pub fn offset(&self, x: usize, y: usize) -> usize {
+        y * self.pitch + x * self.format.bytes_per_pixel()
+    }
+
+    /// Bytes of the pixels of row `y`, without padding.
+    pub fn row_mut(&mut self, y: usize) -> &mut [u8] {
+        let start = y * self.pitch;
+        &mut self.pixels[start..start + self.width * self.format.bytes_per_pixel()]
+    }
+
+    /// Sets a pixel of an indexed buffer; points outside of the buffer are ignored.
+    pub fn plot(&mut self, x: i32, y: i32, index: u8) {
+        if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height {
+            return;
+        }
+        let offset = self.offset(x as usize, y as usize);
+        self.pixels[offset] = index;
+    }
+
+    pub fn fill(&mut self, index: u8) {
+        for y in 0..self.height {
+            self.row_mut(y).fill(index);
+        }
+    }
+
+    /// Converts an indexed buffer of the same size into this one through `palette`.
+    pub fn present(&mut self, screen: &FrameBuffer, palette: &Palette) {
+        let format = self.format;
+        let bytes = format.bytes_per_pixel();
+        for y in 0..self.height.min(screen.height) {
+            let source = &screen.pixels[y * screen.pitch..y * screen.pitch + screen.width];
+            for (pixel, &index) in self.row_mut(y).chunks_exact_mut(bytes).zip(source.iter()) {
+                let [r, g, b] = palette[index as usize];
+                match format {
+                    PixelFormat::Indexed8 => pixel[0] = index,
+                    PixelFormat::Rgb8 => pixel.copy_from_slice(&[r, g, b]),
+                    PixelFormat::Rgba8 => pixel.copy_from_slice(&[r, g, b, 255]),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,41 @@
+use super::data_types::{MTF_EASY, MTF_HARD, MTF_NORMAL};
+
+/// Difficulty chosen for a game, from "I'm too young to die" to "Nightmare!".
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Skill {
+    Baby,
+    Easy,
+    Medium,
+    Hard,
+    Nightmare,
+}
+
+impl Skill {
+    /// Skill for the `-skill` command line option, 1 to 5.
+    pub fn from_number(number: i64) -> Option<Skill> {
+        match number {
+            1 => Some(Skill::Baby),
+            2 => Some(Skill::Easy),
+            3 => Some(Skill::Medium),
+            4 => Some(Skill::Hard),
+            5 => Some(Skill::Nightmare),
+            _ => None,
+        }
+    }
+
+    /// Bit of `Thing::flags` marking the things present at this skill.
+    pub fn thing_flag(&self) -> u16 {
+        match self {
+            Skill::Baby | Skill::Easy => MTF_EASY,
+            Skill::Medium => MTF_NORMAL,
+            Skill::Hard | Skill::Nightmare => MTF_HARD,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GameMode {
+    SinglePlayer,
+    Cooperative,
+    Deathmatch,
+}
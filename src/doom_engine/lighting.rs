@@ -0,0 +1,65 @@
+use super::fixed::Fixed;
+
+/// Number of distinct sector light levels; sector light is 0..=255.
+pub const LIGHT_LEVELS: usize = 16;
+const LIGHT_SEG_SHIFT: i16 = 4;
+/// Number of COLORMAP entries used for lighting, from full bright to black.
+pub const LIGHT_COLORMAPS: usize = 32;
+/// Distance to colormap divisor.
+const DIST_MAP: i32 = 2;
+
+/// Steps of wall scale, and of span distance, per light level, and the
+/// shifts from a scale or distance to its step.
+const MAX_LIGHT_SCALE: usize = 48;
+const LIGHT_SCALE_SHIFT: u32 = 12;
+const MAX_LIGHT_Z: usize = 128;
+const LIGHT_Z_SHIFT: u32 = 20;
+
+/// Colormap indices for every light level at increasing wall scale (walls and
+/// sprites) and at increasing distance (floor and ceiling spans). Far surfaces
+/// get darker colormaps, down to a floor set by the light level.
+pub struct Lights {
+    scale_light: Vec<[usize; MAX_LIGHT_SCALE]>,
+    z_light: Vec<[usize; MAX_LIGHT_Z]>,
+}
+
+impl Lights {
+    /// Tables for a view `width` columns wide; wall scales grow with the width.
+    pub fn new(width: i32) -> Self {
+        let mut scale_light = vec![[0; MAX_LIGHT_SCALE]; LIGHT_LEVELS];
+        let mut z_light = vec![[0; MAX_LIGHT_Z]; LIGHT_LEVELS];
+        for i in 0..LIGHT_LEVELS {
+            let start_map = ((LIGHT_LEVELS - 1 - i) * 2 * LIGHT_COLORMAPS / LIGHT_LEVELS) as i32;
+            for (j, level) in scale_light[i].iter_mut().enumerate() {
+                *level = clamp_level(start_map - j as i32 * 320 / width.max(1) / DIST_MAP);
+            }
+            for (j, level) in z_light[i].iter_mut().enumerate() {
+                let scale = 160 / (j as i32 + 1);
+                *level = clamp_level(start_map - scale / DIST_MAP);
+            }
+        }
+        Lights { scale_light, z_light }
+    }
+
+    /// Table row for a sector light level, `contrast` rows brighter.
+    pub fn light_index(light_level: i16, contrast: i32) -> usize {
+        let light = (light_level.max(0) >> LIGHT_SEG_SHIFT) as i32 + contrast;
+        light.clamp(0, LIGHT_LEVELS as i32 - 1) as usize
+    }
+
+    /// Colormap for a wall column or sprite drawn at `scale`.
+    pub fn scale_colormap(&self, light: usize, scale: Fixed) -> usize {
+        let index = ((scale.0 >> LIGHT_SCALE_SHIFT).max(0) as usize).min(MAX_LIGHT_SCALE - 1);
+        self.scale_light[light][index]
+    }
+
+    /// Colormap for a floor or ceiling row at `distance` from the viewer.
+    pub fn z_colormap(&self, light: usize, distance: Fixed) -> usize {
+        let index = ((distance.0 >> LIGHT_Z_SHIFT).max(0) as usize).min(MAX_LIGHT_Z - 1);
+        self.z_light[light][index]
+    }
+}
+
+fn clamp_level(level: i32) -> usize {
+    level.clamp(0, LIGHT_COLORMAPS as i32 - 1) as usize
+}
@@ -0,0 +1,366 @@
+use line_drawing::Bresenham;
+
+use super::blockmap::BLOCK_SIZE;
+use super::bsp_debug::BspDebug;
+use super::data_types::*;
+use super::frame_buffer::FrameBuffer;
+use super::player::Player;
+use super::wad_data::WadData;
+use super::KeyState;
+
+// Palette indices of the automap colors.
+pub const WHITE: u8 = 209;
+pub const REDS: u8 = 176;
+pub const BROWNS: u8 = 64;
+pub const YELLOWS: u8 = 231;
+pub const GRAYS: u8 = 96;
+pub const GREENS: u8 = 112;
+
+const WALL_COLOR: u8 = REDS;
+const FLOOR_CHANGE_COLOR: u8 = BROWNS;
+const CEILING_CHANGE_COLOR: u8 = YELLOWS;
+/// Two-sided lines without a height change, only shown in debug mode.
+const FLAT_LINE_COLOR: u8 = GRAYS;
+/// Lines not seen yet, shown by the computer area map.
+const UNSEEN_COLOR: u8 = GRAYS + 3;
+const SPECIAL_COLOR: u8 = GREENS;
+const GRID_COLOR: u8 = GRAYS + 8;
+
+/// Zoom factor per update while a zoom key is held.
+const ZOOM_STEP: f32 = 1.02;
+/// Screen pixels panned per update while an arrow key is held.
+const PAN_STEP: f32 = 4.0;
+/// Map units shown across the screen height at the closest zoom.
+const MIN_VIEW_HEIGHT: f32 = 32.0;
+const MAX_MARKS: usize = 10;
+/// Half the length of the player arrow, in map units.
+const ARROW_SIZE: f32 = 16.0 * 8.0 / 7.0;
+/// Half the size of a mark cross, in pixels.
+const MARK_SIZE: i32 = 3;
+
+/// The 2D map view: the map lines seen from above, scaled and moved around
+/// independently of the player, with a grid and marks placed by the player.
+pub struct Automap {
+    pub active: bool,
+    /// Keep the player at the center of the screen.
+    pub follow: bool,
+    /// Turn the map so that the player always faces up.
+    pub rotate: bool,
+    /// Show the blockmap grid.
+    pub grid: bool,
+    /// The player has the computer area map: lines not seen yet are shown too.
+    pub computer_map: bool,
+    /// Show every line, including hidden ones, and highlight lines with specials.
+    pub debug: bool,
+    /// Map point at the center of the screen.
+    center: (f32, f32),
+    /// Screen pixels per map unit.
+    scale: f32,
+    /// Zoom out to fit the whole map on the next frame.
+    fit_pending: bool,
+    /// Corners of the bounding box of all vertexes.
+    min: (f32, f32),
+    max: (f32, f32),
+    marks: Vec<(f32, f32)>,
+    next_mark: usize,
+    /// Linedefs the player has seen in the first-person view.
+    seen_lines: Vec<bool>,
+}
+
+impl Automap {
+    pub fn new(wad_data: &WadData) -> Self {
+        let vertexes = &wad_data.vertexes;
+        let mut min = (f32::MAX, f32::MAX);
+        let mut max = (f32::MIN, f32::MIN);
+        for v in vertexes {
+            min = (min.0.min(v.x as f32), min.1.min(v.y as f32));
+            max = (max.0.max(v.x as f32), max.1.max(v.y as f32));
+        }
+        if vertexes.is_empty() {
+            (min, max) = ((0.0, 0.0), (0.0, 0.0));
+        }
+        Automap {
+            active: false,
+            follow: false,
+            rotate: false,
+            grid: false,
+            computer_map: false,
+            debug: false,
+            center: ((min.0 + max.0) / 2.0, (min.1 + max.1) / 2.0),
+            scale: 1.0,
+            fit_pending: true,
+            min,
+            max,
+            marks: Vec::new(),
+            next_mark: 0,
+            seen_lines: wad_data.linedefs.iter().map(|l| l.flags & ML_MAPPED != 0).collect(),
+        }
+    }
+
+    /// Records the lines drawn in a frame of the first-person view.
+    pub fn mark_seen(&mut self, seen_lines: &[bool]) {
+        for (seen, &drawn) in self.seen_lines.iter_mut().zip(seen_lines) {
+            *seen |= drawn;
+        }
+    }
+
+    /// Opens the map zoomed out to fit the whole map, or closes it.
+    pub fn toggle(&mut self) {
+        self.active = !self.active;
+        if self.active {
+            self.center = ((self.min.0 + self.max.0) / 2.0, (self.min.1 + self.max.1) / 2.0);
+            self.fit_pending = true;
+        }
+    }
+
+    /// Marks the point at the center of the screen. After `MAX_MARKS` marks
+    /// the oldest ones are replaced.
+    pub fn add_mark(&mut self) {
+        if self.marks.len() < MAX_MARKS {
+            self.marks.push(self.center);
+        } else {
+            self.marks[self.next_mark] = self.center;
+        }
+        self.next_mark = (self.next_mark + 1) % MAX_MARKS;
+    }
+
+    pub fn clear_marks(&mut self) {
+        self.marks.clear();
+        self.next_mark = 0;
+    }
+
+    /// Zooms with the zoom keys and, unless following the player, pans with
+    /// the arrow keys.
+    pub fn update(&mut self, key_state: &KeyState, player: &Player) {
+        if key_state.zoom_in {
+            self.scale *= ZOOM_STEP;
+        }
+        if key_state.zoom_out {
+            self.scale /= ZOOM_STEP;
+        }
+
+        if self.follow {
+            self.center = (player.pos.0.to_f32(), player.pos.1.to_f32());
+            return;
+        }
+        let mut pan = (0.0, 0.0);
+        if key_state.left {
+            pan.0 -= PAN_STEP;
+        }
+        if key_state.right {
+            pan.0 += PAN_STEP;
+        }
+        if key_state.up {
+            pan.1 += PAN_STEP;
+        }
+        if key_state.down {
+            pan.1 -= PAN_STEP;
+        }
+        // Screen directions are turned back into map directions.
+        let (sin, cos) = (-self.rotation(player)).sin_cos();
+        self.center.0 += (pan.0 * cos - pan.1 * sin) / self.scale;
+        self.center.1 += (pan.0 * sin + pan.1 * cos) / self.scale;
+        self.center.0 = self.center.0.clamp(self.min.0, self.max.0);
+        self.center.1 = self.center.1.clamp(self.min.1, self.max.1);
+    }
+
+    /// Angle in radians the map is turned by on screen.
+    fn rotation(&self, player: &Player) -> f32 {
+        if self.rotate {
+            (90.0 - player.angle.to_degrees()).to_radians()
+        } else {
+            0.0
+        }
+    }
+
+    /// Draws the map, with the BSP traversal over it if `bsp_debug` is given.
+    pub fn draw(&mut self, screen: &mut FrameBuffer, wad_data: &WadData, player: &Player, bsp_debug: Option<&BspDebug>) {
+        let (width, height) = (screen.width as f32, screen.height as f32);
+        // Zoomed all the way out, the whole map fits the screen.
+        let map_size = ((self.max.0 - self.min.0).max(1.0), (self.max.1 - self.min.1).max(1.0));
+        let min_scale = ((width - 1.0) / map_size.0).min((height - 1.0) / map_size.1);
+        let max_scale = (height / MIN_VIEW_HEIGHT).max(min_scale);
+        if self.fit_pending {
+            self.scale = min_scale;
+            self.fit_pending = false;
+        }
+        self.scale = self.scale.clamp(min_scale, max_scale);
+        if self.follow {
+            self.center = (player.pos.0.to_f32(), player.pos.1.to_f32());
+        }
+
+        let (sin, cos) = self.rotation(player).sin_cos();
+        let view = MapView {
+            center: self.center,
+            scale: self.scale,
+            sin,
+            cos,
+            width,
+            height,
+        };
+
+        if self.grid {
+            self.draw_grid(screen, wad_data, &view);
+        }
+        for (id, l) in wad_data.linedefs.iter().enumerate() {
+            let Some(color) = self.line_color(wad_data, id, l) else {
+                continue;
+            };
+            let v1 = &wad_data.vertexes[l.start_vertex_id as usize];
+            let v2 = &wad_data.vertexes[l.end_vertex_id as usize];
+            view.line(screen, (v1.x as f32, v1.y as f32), (v2.x as f32, v2.y as f32), color);
+        }
+        if let Some(bsp_debug) = bsp_debug {
+            bsp_debug.draw(screen, wad_data, &view);
+        }
+        self.draw_player(screen, player, &view);
+        for &mark in &self.marks {
+            let (x, y) = view.to_screen(mark);
+            let (x, y) = (x.round() as i32, y.round() as i32);
+            for d in -MARK_SIZE..=MARK_SIZE {
+                screen.plot(x + d, y, YELLOWS);
+                screen.plot(x, y + d, YELLOWS);
+            }
+        }
+    }
+
+    /// Color of a linedef on the map, or None if it is not shown: walls in red,
+    /// steps in brown, ceiling changes in yellow. Secret lines pass for walls.
+    fn line_color(&self, wad_data: &WadData, id: usize, linedef: &Linedef) -> Option<u8> {
+        if self.debug && linedef.line_type != 0 {
+            return Some(SPECIAL_COLOR);
+        }
+        if !self.debug {
+            if linedef.flags & ML_DONTDRAW != 0 {
+                return None;
+            }
+            if !self.seen_lines[id] {
+                return self.computer_map.then_some(UNSEEN_COLOR);
+            }
+        }
+
+        let (Some(front), Some(back)) = (wad_data.front_sector(linedef), wad_data.back_sector(linedef)) else {
+            return Some(WALL_COLOR);
+        };
+        if linedef.flags & ML_SECRET != 0 && !self.debug {
+            Some(WALL_COLOR)
+        } else if front.floor_height != back.floor_height {
+            Some(FLOOR_CHANGE_COLOR)
+        } else if front.ceiling_height != back.ceiling_height {
+            Some(CEILING_CHANGE_COLOR)
+        } else if self.debug {
+            Some(FLAT_LINE_COLOR)
+        } else {
+            None
+        }
+    }
+
+    /// Lines along the blockmap blocks over the visible part of the map.
+    fn draw_grid(&self, screen: &mut FrameBuffer, wad_data: &WadData, view: &MapView) {
+        let blockmap = &wad_data.blockmap;
+        let block = BLOCK_SIZE as f32;
+        let min = (blockmap.origin_x as f32, blockmap.origin_y as f32);
+        let max = (min.0 + blockmap.columns as f32 * block, min.1 + blockmap.rows as f32 * block);
+        // Half the screen diagonal covers the screen at any rotation.
+        let reach = (view.width.hypot(view.height) / 2.0) / view.scale;
+        let (left, right) = ((self.center.0 - reach).max(min.0), (self.center.0 + reach).min(max.0));
+        let (bottom, top) = ((self.center.1 - reach).max(min.1), (self.center.1 + reach).min(max.1));
+
+        let mut x = min.0 + ((left - min.0) / block).ceil() * block;
+        while x <= right {
+            view.line(screen, (x, bottom), (x, top), GRID_COLOR);
+            x += block;
+        }
+        let mut y = min.1 + ((bottom - min.1) / block).ceil() * block;
+        while y <= top {
+            view.line(screen, (left, y), (right, y), GRID_COLOR);
+            y += block;
+        }
+    }
+
+    /// An arrow at the player position, pointing the way the player faces.
+    fn draw_player(&self, screen: &mut FrameBuffer, player: &Player, view: &MapView) {
+        let r = ARROW_SIZE;
+        let arrow = [
+            ((-r + r / 8.0, 0.0), (r, 0.0)),
+            ((r, 0.0), (r - r / 2.0, r / 4.0)),
+            ((r, 0.0), (r - r / 2.0, -r / 4.0)),
+            ((-r + r / 8.0, 0.0), (-r - r / 8.0, r / 4.0)),
+            ((-r + r / 8.0, 0.0), (-r - r / 8.0, -r / 4.0)),
+            ((-r + 3.0 * r / 8.0, 0.0), (-r + r / 8.0, r / 4.0)),
+            ((-r + 3.0 * r / 8.0, 0.0), (-r + r / 8.0, -r / 4.0)),
+        ];
+        let (sin, cos) = (player.angle.sin().to_f32(), player.angle.cos().to_f32());
+        let (px, py) = (player.pos.0.to_f32(), player.pos.1.to_f32());
+        let place = |(x, y): (f32, f32)| (px + x * cos - y * sin, py + x * sin + y * cos);
+        for (start, end) in arrow {
+            view.line(screen, place(start), place(end), WHITE);
+        }
+    }
+}
+
+/// Placement of the map on the screen for one frame.
+pub struct MapView {
+    center: (f32, f32),
+    scale: f32,
+    sin: f32,
+    cos: f32,
+    width: f32,
+    height: f32,
+}
+
+impl MapView {
+    pub fn to_screen(&self, (x, y): (f32, f32)) -> (f32, f32) {
+        let (dx, dy) = (x - self.center.0, y - self.center.1);
+        let (rx, ry) = (dx * self.cos - dy * self.sin, dx * self.sin + dy * self.cos);
+        // Pixel centers are at whole coordinates, so the middle of the screen
+        // is half a pixel before `width / 2`.
+        let (mid_x, mid_y) = ((self.width - 1.0) / 2.0, (self.height - 1.0) / 2.0);
+        (mid_x + rx * self.scale, mid_y - ry * self.scale)
+    }
+
+    /// Draws the map line from `a` to `b`, clipped to the screen.
+    pub fn line(&self, screen: &mut FrameBuffer, a: (f32, f32), b: (f32, f32), color: u8) {
+        // Keep the points that round to pixels on the screen.
+        let screen_min = (-0.5, -0.5);
+        let screen_max = (self.width - 0.5, self.height - 0.5);
+        let Some((a, b)) = clip_line(self.to_screen(a), self.to_screen(b), screen_min, screen_max) else {
+            return;
+        };
+        let a = (a.0.round() as i32, a.1.round() as i32);
+        let b = (b.0.round() as i32, b.1.round() as i32);
+        for (x, y) in Bresenham::new(a, b) {
+            screen.plot(x, y, color);
+        }
+    }
+}
+
+/// Part of the segment from `a` to `b` inside the rectangle from `min` to
+/// `max` (Liang-Barsky), or None if it is entirely outside.
+pub fn clip_line(
+    a: (f32, f32),
+    b: (f32, f32),
+    min: (f32, f32),
+    max: (f32, f32),
+) -> Option<((f32, f32), (f32, f32))> {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let (mut t0, mut t1) = (0.0f32, 1.0f32);
+    for (p, q) in [(-dx, a.0 - min.0), (dx, max.0 - a.0), (-dy, a.1 - min.1), (dy, max.1 - a.1)] {
+        if p == 0.0 {
+            if q < 0.0 {
+                return None;
+            }
+        } else {
+            let t = q / p;
+            if p < 0.0 {
+                t0 = t0.max(t);
+            } else {
+                t1 = t1.min(t);
+            }
+        }
+    }
+    if t0 > t1 {
+        return None;
+    }
+    Some(((a.0 + t0 * dx, a.1 + t0 * dy), (a.0 + t1 * dx, a.1 + t1 * dy)))
+}
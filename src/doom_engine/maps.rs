@@ -0,0 +1,99 @@
+use std::fmt;
+
+/// Name of a map marker, in either the Doom 1 (`ExMy`) or the Doom 2 (`MAPxx`) convention.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MapName {
+    Episode { episode: u8, map: u8 },
+    Numbered(u8),
+}
+
+impl MapName {
+    /// Parses names such as `E1M3`, `e1m3` or `MAP07`, ignoring NUL padding.
+    /// Episodes and maps count from 1, so `E0M1`, `E1M0` and `MAP00` are not
+    /// map names.
+    pub fn parse(name: &str) -> Option<MapName> {
+        let name = name.trim_end_matches('\0').trim().to_ascii_uppercase();
+        let b = name.as_bytes();
+        let is_number = |c: u8| (b'1'..=b'9').contains(&c);
+        if b.len() == 4 && b[0] == b'E' && b[2] == b'M' && is_number(b[1]) && is_number(b[3]) {
+            return Some(MapName::Episode {
+                episode: b[1] - b'0',
+                map: b[3] - b'0',
+            });
+        }
+        if b.len() == 5 && name.starts_with("MAP") && b[3].is_ascii_digit() && b[4].is_ascii_digit() {
+            let map = (b[3] - b'0') * 10 + b[4] - b'0';
+            return (map > 0).then_some(MapName::Numbered(map));
+        }
+        None
+    }
+
+    /// Name of the sky texture: one per episode in Doom 1, one per third of
+    /// the maps in Doom 2.
+    pub fn sky_texture(&self) -> [u8; 8] {
+        let sky = match *self {
+            MapName::Episode { episode, .. } => episode.clamp(1, 4),
+            MapName::Numbered(map) if map < 12 => 1,
+            MapName::Numbered(map) if map < 21 => 2,
+            MapName::Numbered(_) => 3,
+        };
+        [b'S', b'K', b'Y', b'0' + sky, 0, 0, 0, 0]
+    }
+}
+
+impl fmt::Display for MapName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MapName::Episode { episode, map } => write!(f, "E{episode}M{map}"),
+            MapName::Numbered(map) => write!(f, "MAP{map:02}"),
+        }
+    }
+}
+
+/// A map found in the merged directory.
+#[derive(Clone, Copy)]
+pub struct MapEntry {
+    pub name: MapName,
+    /// Directory index of the map marker; the map lumps follow it.
+    pub lump_index: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn episode_and_map() {
+        assert_eq!(MapName::parse("E1M3"), Some(MapName::Episode { episode: 1, map: 3 }));
+        assert_eq!(MapName::parse("e4m9"), Some(MapName::Episode { episode: 4, map: 9 }));
+        assert_eq!(MapName::parse("E2m1\0\0\0\0"), Some(MapName::Episode { episode: 2, map: 1 }));
+    }
+
+    #[test]
+    fn numbered_map() {
+        assert_eq!(MapName::parse("MAP07"), Some(MapName::Numbered(7)));
+        assert_eq!(MapName::parse("map32"), Some(MapName::Numbered(32)));
+        assert_eq!(MapName::parse("Map99\0\0\0"), Some(MapName::Numbered(99)));
+    }
+
+    #[test]
+    fn out_of_range() {
+        for name in ["E0M1", "E1M0", "MAP00"] {
+            assert_eq!(MapName::parse(name), None, "{name}");
+        }
+    }
+
+    #[test]
+    fn not_map_names() {
+        for name in ["", "E1M", "E1M10", "E1M1X", "EAM1", "E1X1", "MAP7", "MAP071", "MAP7X", "MAPXX", "THINGS"] {
+            assert_eq!(MapName::parse(name), None, "{name}");
+        }
+    }
+
+    #[test]
+    fn display_round_trips() {
+        for name in ["E1M3", "MAP07", "MAP32"] {
+            assert_eq!(MapName::parse(name).unwrap().to_string(), name);
+        }
+    }
+}
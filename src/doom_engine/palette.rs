@@ -0,0 +1,2 @@
+pub type Palette = [[u8; 3]; 256];
+pub type Colormap = [u8; 256];
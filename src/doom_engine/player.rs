@@ -1,8 +1,15 @@
 use super::KeyState;
+use super::angle::{Angle, ANG1};
+use super::fixed::Fixed;
+
+/// Map units moved per update while a movement key is held.
+const MOVE_STEP: Fixed = Fixed::from_int(2);
+/// Turned per update while a turn key is held.
+const TURN_STEP: Angle = Angle(2 * ANG1);
 
 pub struct Player {
-    pub pos: (i16,i16),
-    pub angle: i16,
+    pub pos: (Fixed, Fixed),
+    pub angle: Angle,
 }
 
 impl Player {
@@ -12,22 +19,22 @@ impl Player {
 
     fn control(&mut self, key_state: &KeyState){
         if key_state.w {
-            self.pos.1 += 2;
+            self.pos.1 += MOVE_STEP;
         }
         if key_state.s {
-            self.pos.1 -= 2;
+            self.pos.1 -= MOVE_STEP;
         }
         if key_state.a {
-            self.pos.0 -= 2;
+            self.pos.0 -= MOVE_STEP;
         }
         if key_state.d {
-            self.pos.0 += 2;
+            self.pos.0 += MOVE_STEP;
         }
         if key_state.left {
-            self.angle += 2;
+            self.angle += TURN_STEP;
         }
         if key_state.right {
-            self.angle -= 2;
+            self.angle -= TURN_STEP;
         }
     }
-}
\ No newline at end of file
+}
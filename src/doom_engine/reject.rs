@@ -0,0 +1,88 @@
+/// Sector-to-sector visibility matrix from the REJECT lump.
+///
+/// Bit `a * sector_count + b`, counting from the lowest bit of the first
+/// byte, is set when no point of sector `b` can be seen from sector `a`.
+pub struct Reject {
+    pub sector_count: usize,
+    bits: Vec<u8>,
+}
+
+impl Reject {
+    /// Missing bits (short or empty lumps) are treated as "visible".
+    pub fn new(sector_count: usize, bits: Vec<u8>) -> Self {
+        Reject { sector_count, bits }
+    }
+
+    pub fn is_rejected(&self, sector_a: usize, sector_b: usize) -> bool {
+        if sector_a >= self.sector_count || sector_b >= self.sector_count {
+            return false;
+        }
+        let bit = sector_a * self.sector_count + sector_b;
+        match self.bits.get(bit / 8) {
+            Some(byte) => byte & (1 << (bit % 8)) != 0,
+            None => false,
+        }
+    }
+
+    pub fn can_see(&self, sector_a: usize, sector_b: usize) -> bool {
+        !self.is_rejected(sector_a, sector_b)
+    }
+
+    /// Pairs of distinct sectors `(a, b)`, `a < b`, that are rejected in both directions.
+    pub fn mutually_rejected_pairs(&self) -> Vec<(usize, usize)> {
+        let mut pairs = Vec::new();
+        for a in 0..self.sector_count {
+            for b in a + 1..self.sector_count {
+                if self.is_rejected(a, b) && self.is_rejected(b, a) {
+                    pairs.push((a, b));
+                }
+            }
+        }
+        pairs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bits_are_row_major_and_lowest_first() {
+        // 3 sectors, 9 bits: 0 cannot see 1 (bit 1), 1 cannot see 2 (bit 5),
+        // 2 cannot see 0 (bit 6) nor 2 (bit 8, in the second byte).
+        let reject = Reject::new(3, vec![0b0110_0010, 0b0000_0001]);
+        let hidden = [(0, 1), (1, 2), (2, 0), (2, 2)];
+        for a in 0..3 {
+            for b in 0..3 {
+                assert_eq!(reject.can_see(a, b), !hidden.contains(&(a, b)), "{a} -> {b}");
+            }
+        }
+    }
+
+    #[test]
+    fn short_lump() {
+        // Only the first 8 of 16 bits are present.
+        let reject = Reject::new(4, vec![0b1000_0000]);
+        assert!(!reject.can_see(1, 3));
+        assert!(reject.can_see(2, 0));
+        assert!(reject.can_see(3, 3));
+        assert!(Reject::new(4, Vec::new()).can_see(1, 3));
+    }
+
+    #[test]
+    fn sectors_out_of_range() {
+        let reject = Reject::new(2, vec![0xFF]);
+        assert!(!reject.can_see(1, 1));
+        assert!(reject.can_see(2, 0));
+        assert!(reject.can_see(0, 2));
+    }
+
+    #[test]
+    fn mutually_rejected_pairs() {
+        // 0 and 1 reject each other, 1 rejects 2 but not the other way round,
+        // and 2 rejects itself.
+        let reject = Reject::new(3, vec![0b0010_1010, 0b0000_0001]);
+        assert_eq!(reject.mutually_rejected_pairs(), vec![(0, 1)]);
+        assert!(Reject::new(3, Vec::new()).mutually_rejected_pairs().is_empty());
+    }
+}
@@ -0,0 +1,194 @@
+use std::collections::HashMap;
+
+use super::data_types::*;
+
+/// One animation frame of a sprite, seen from up to eight directions.
+#[derive(Clone, Default)]
+pub struct SpriteFrame {
+    /// Whether the frame has a picture per direction; otherwise `pictures[0]`
+    /// is used from every direction.
+    pub rotate: bool,
+    /// Picture ids per direction, starting with the thing facing the viewer
+    /// and going counterclockwise in 45 degree steps.
+    pub pictures: [Option<usize>; 8],
+    /// Whether the picture is drawn mirrored.
+    pub flip: [bool; 8],
+}
+
+/// All sprites of the WAD stack: the pictures between S_START and S_END,
+/// grouped by their four-letter sprite name.
+pub struct Sprites {
+    pub pictures: Vec<Picture>,
+    frames: HashMap<[u8; 4], Vec<SpriteFrame>>,
+}
+
+impl Sprites {
+    /// Groups sprite lumps by name. Each lump is named after its sprite, frame
+    /// letter and rotation (`TROOA1`); a second frame and rotation (`TROOA2A8`)
+    /// reuse the picture mirrored.
+    pub fn new(lumps: Vec<([u8; 8], Picture)>) -> Self {
+        let mut pictures = Vec::with_capacity(lumps.len());
+        let mut frames: HashMap<[u8; 4], Vec<SpriteFrame>> = HashMap::new();
+        for (name, picture) in lumps {
+            let id = pictures.len();
+            pictures.push(picture);
+
+            let sprite = [name[0], name[1], name[2], name[3]];
+            let frames = frames.entry(sprite).or_default();
+            install(frames, name[4], name[5], id, false);
+            if name[6] != 0 {
+                install(frames, name[6], name[7], id, true);
+            }
+        }
+        Sprites { pictures, frames }
+    }
+
+    pub fn frame(&self, sprite: &[u8; 4], frame: u8) -> Option<&SpriteFrame> {
+        self.frames.get(sprite)?.get(frame.checked_sub(b'A')? as usize)
+    }
+}
+
+/// Stores a picture as `rotation` (`'0'` for all directions) of `frame`.
+fn install(frames: &mut Vec<SpriteFrame>, frame: u8, rotation: u8, picture: usize, flip: bool) {
+    let (frame, rotation) = match (frame.checked_sub(b'A'), rotation.checked_sub(b'0')) {
+        (Some(frame), Some(rotation)) if frame < 29 && rotation <= 8 => (frame as usize, rotation as usize),
+        _ => return,
+    };
+    if frames.len() <= frame {
+        frames.resize(frame + 1, SpriteFrame::default());
+    }
+    let sprite_frame = &mut frames[frame];
+    if rotation == 0 {
+        sprite_frame.rotate = false;
+        sprite_frame.pictures = [Some(picture); 8];
+        sprite_frame.flip = [flip; 8];
+    } else {
+        sprite_frame.rotate = true;
+        sprite_frame.pictures[rotation - 1] = Some(picture);
+        sprite_frame.flip[rotation - 1] = flip;
+    }
+}
+
+/// Sprite name and frame a thing type is drawn with when the map starts;
+/// `None` for things without a sprite, such as player starts.
+pub fn thing_sprite(ttype: u16) -> Option<(&'static [u8; 4], u8)> {
+    let sprite = match ttype {
+        // Monsters
+        3004 => (b"POSS", b'A'),
+        9 => (b"SPOS", b'A'),
+        65 => (b"CPOS", b'A'),
+        3001 => (b"TROO", b'A'),
+        3002 | 58 => (b"SARG", b'A'),
+        3006 => (b"SKUL", b'A'),
+        3005 => (b"HEAD", b'A'),
+        69 => (b"BOS2", b'A'),
+        3003 => (b"BOSS", b'A'),
+        68 => (b"BSPI", b'A'),
+        71 => (b"PAIN", b'A'),
+        66 => (b"SKEL", b'A'),
+        67 => (b"FATT", b'A'),
+        64 => (b"VILE", b'A'),
+        7 => (b"SPID", b'A'),
+        16 => (b"CYBR", b'A'),
+        84 => (b"SSWV", b'A'),
+        72 => (b"KEEN", b'A'),
+        88 => (b"BBRN", b'A'),
+        // Weapons
+        2005 => (b"CSAW", b'A'),
+        2001 => (b"SHOT", b'A'),
+        82 => (b"SGN2", b'A'),
+        2002 => (b"MGUN", b'A'),
+        2003 => (b"LAUN", b'A'),
+        2004 => (b"PLAS", b'A'),
+        2006 => (b"BFUG", b'A'),
+        // Ammunition
+        2007 => (b"CLIP", b'A'),
+        2048 => (b"AMMO", b'A'),
+        2008 => (b"SHEL", b'A'),
+        2049 => (b"SBOX", b'A'),
+        2010 => (b"ROCK", b'A'),
+        2046 => (b"BROK", b'A'),
+        2047 => (b"CELL", b'A'),
+        17 => (b"CELP", b'A'),
+        8 => (b"BPAK", b'A'),
+        // Health, armor and powerups
+        2011 => (b"STIM", b'A'),
+        2012 => (b"MEDI", b'A'),
+        2014 => (b"BON1", b'A'),
+        2015 => (b"BON2", b'A'),
+        2018 => (b"ARM1", b'A'),
+        2019 => (b"ARM2", b'A'),
+        2013 => (b"SOUL", b'A'),
+        83 => (b"MEGA", b'A'),
+        2022 => (b"PINV", b'A'),
+        2023 => (b"PSTR", b'A'),
+        2024 => (b"PINS", b'A'),
+        2025 => (b"SUIT", b'A'),
+        2026 => (b"PMAP", b'A'),
+        2045 => (b"PVIS", b'A'),
+        // Keys
+        5 => (b"BKEY", b'A'),
+        6 => (b"YKEY", b'A'),
+        13 => (b"RKEY", b'A'),
+        40 => (b"BSKU", b'A'),
+        39 => (b"YSKU", b'A'),
+        38 => (b"RSKU", b'A'),
+        // Obstacles and decorations
+        2035 => (b"BAR1", b'A'),
+        70 => (b"FCAN", b'A'),
+        2028 => (b"COLU", b'A'),
+        85 => (b"TLMP", b'A'),
+        86 => (b"TLP2", b'A'),
+        34 => (b"CAND", b'A'),
+        35 => (b"CBRA", b'A'),
+        44 => (b"TBLU", b'A'),
+        45 => (b"TGRN", b'A'),
+        46 => (b"TRED", b'A'),
+        55 => (b"SMBT", b'A'),
+        56 => (b"SMGT", b'A'),
+        57 => (b"SMRT", b'A'),
+        48 => (b"ELEC", b'A'),
+        30 => (b"COL1", b'A'),
+        31 => (b"COL2", b'A'),
+        32 => (b"COL3", b'A'),
+        33 => (b"COL4", b'A'),
+        37 => (b"COL6", b'A'),
+        36 => (b"COL5", b'A'),
+        41 => (b"CEYE", b'A'),
+        42 => (b"FSKU", b'A'),
+        43 => (b"TRE1", b'A'),
+        54 => (b"TRE2", b'A'),
+        47 => (b"SMIT", b'A'),
+        25 => (b"POL1", b'A'),
+        26 => (b"POL6", b'A'),
+        27 => (b"POL4", b'A'),
+        28 => (b"POL2", b'A'),
+        29 => (b"POL3", b'A'),
+        24 => (b"POL5", b'A'),
+        49 | 63 => (b"GOR1", b'A'),
+        50 | 59 => (b"GOR2", b'A'),
+        51 | 61 => (b"GOR3", b'A'),
+        52 | 60 => (b"GOR4", b'A'),
+        53 | 62 => (b"GOR5", b'A'),
+        73 => (b"HDB1", b'A'),
+        74 => (b"HDB2", b'A'),
+        75 => (b"HDB3", b'A'),
+        76 => (b"HDB4", b'A'),
+        77 => (b"HDB5", b'A'),
+        78 => (b"HDB6", b'A'),
+        79 => (b"POB1", b'A'),
+        80 => (b"POB2", b'A'),
+        81 => (b"BRS1", b'A'),
+        // Corpses
+        10 | 12 => (b"PLAY", b'W'),
+        15 => (b"PLAY", b'N'),
+        18 => (b"POSS", b'L'),
+        19 => (b"SPOS", b'L'),
+        20 => (b"TROO", b'M'),
+        21 => (b"SARG", b'N'),
+        22 => (b"HEAD", b'L'),
+        23 => (b"SKUL", b'K'),
+        _ => return None,
+    };
+    Some(sprite)
+}
@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+
+use super::data_types::*;
+use super::wad_reader::name_key;
+
+/// A composite wall texture, stored column by column.
+pub struct Texture {
+    pub name: [u8; 8],
+    pub width: usize,
+    pub height: usize,
+    /// Palette indices, `height` entries per column.
+    pub pixels: Vec<u8>,
+    /// Whether each entry of `pixels` is covered by a patch.
+    pub opaque: Vec<bool>,
+}
+
+impl Texture {
+    /// Assembles a texture from its definition and the patches listed in PNAMES.
+    /// Missing patches leave holes in the texture.
+    pub fn compose(def: &TextureDef, patches: &[Option<Picture>]) -> Self {
+        let width = def.width as usize;
+        let height = def.height as usize;
+        let mut pixels = vec![0; width * height];
+        let mut opaque = vec![false; width * height];
+
+        for patch_ref in def.patches.iter() {
+            let patch = match patches.get(patch_ref.patch_id as usize) {
+                Some(Some(patch)) => patch,
+                _ => continue,
+            };
+            for (px, posts) in patch.columns.iter().enumerate() {
+                let x = patch_ref.origin_x as i32 + px as i32;
+                if x < 0 || x >= width as i32 {
+                    continue;
+                }
+                let column = x as usize * height;
+                for post in posts.iter() {
+                    for (i, &p) in post.pixels.iter().enumerate() {
+                        let y = patch_ref.origin_y as i32 + (post.top_delta + i) as i32;
+                        if y < 0 || y >= height as i32 {
+                            continue;
+                        }
+                        pixels[column + y as usize] = p;
+                        opaque[column + y as usize] = true;
+                    }
+                }
+            }
+        }
+
+        Texture {
+            name: def.name,
+            width,
+            height,
+            pixels,
+            opaque,
+        }
+    }
+
+    /// Column `x`, wrapping around the texture width.
+    pub fn column(&self, x: i32) -> &[u8] {
+        let x = x.rem_euclid(self.width as i32) as usize;
+        &self.pixels[x * self.height..(x + 1) * self.height]
+    }
+
+    /// Opacity of the pixels of column `x`, wrapping around the texture width.
+    pub fn column_mask(&self, x: i32) -> &[bool] {
+        let x = x.rem_euclid(self.width as i32) as usize;
+        &self.opaque[x * self.height..(x + 1) * self.height]
+    }
+}
+
+/// All composite textures of the WAD stack, addressable by name.
+pub struct Textures {
+    pub textures: Vec<Texture>,
+    by_name: HashMap<[u8; 8], usize>,
+}
+
+impl Textures {
+    pub fn new(textures: Vec<Texture>) -> Self {
+        let mut by_name = HashMap::new();
+        for (i, t) in textures.iter().enumerate() {
+            // The first definition of a name wins, as in the original engine.
+            by_name.entry(name_key(&t.name)).or_insert(i);
+        }
+        Textures { textures, by_name }
+    }
+
+    /// Index of the texture called `name`; `None` for `-` (no texture) and unknown names.
+    pub fn id(&self, name: &[u8; 8]) -> Option<usize> {
+        self.by_name.get(&name_key(name)).copied()
+    }
+
+    pub fn get(&self, name: &[u8; 8]) -> Option<&Texture> {
+        self.id(name).map(|i| &self.textures[i])
+    }
+}
+
+/// Width and height of a flat.
+pub const FLAT_SIZE: usize = 64;
+
+/// A floor or ceiling texture: 64x64 palette indices, row by row.
+pub struct Flat {
+    pub name: [u8; 8],
+    pub pixels: Vec<u8>,
+}
+
+/// All flats of the WAD stack, addressable by name.
+pub struct Flats {
+    pub flats: Vec<Flat>,
+    by_name: HashMap<[u8; 8], usize>,
+}
+
+impl Flats {
+    pub fn new(flats: Vec<Flat>) -> Self {
+        let by_name = flats
+            .iter()
+            .enumerate()
+            .map(|(i, f)| (name_key(&f.name), i))
+            .collect();
+        Flats { flats, by_name }
+    }
+
+    pub fn id(&self, name: &[u8; 8]) -> Option<usize> {
+        self.by_name.get(&name_key(name)).copied()
+    }
+}
@@ -0,0 +1,1294 @@
+use super::angle::{Angle, ANG180, ANG45, ANG90, ANGLE_TO_FINE_SHIFT};
+use super::bsp::BSP;
+use super::bsp_debug::BspStep;
+use super::data_types::*;
+use super::fixed::{Fixed, FRAC_BITS, FRAC_UNIT};
+use super::frame_buffer::{FrameBuffer, PixelFormat};
+use super::lighting::Lights;
+use super::palette::Colormap;
+use super::player::Player;
+use super::sprites::thing_sprite;
+use super::tables::{FINE_ANGLES, FINE_TANGENT, SLOPE_RANGE, TAN_TO_ANGLE};
+use super::textures::{Texture, FLAT_SIZE};
+use super::visplanes::{Visplanes, UNSET};
+use super::wad_data::WadData;
+
+/// Horizontal field of view, in fine angles: 90 degrees.
+const FIELD_OF_VIEW: usize = FINE_ANGLES / 4;
+/// Height of the player's eyes above the floor.
+pub const VIEW_HEIGHT: Fixed = Fixed::from_int(41);
+
+const MIN_SCALE: Fixed = Fixed(256);
+const MAX_SCALE: Fixed = Fixed::from_int(64);
+/// Shift from a view angle to a sky texture column (1024 columns per turn),
+/// texture row at the horizon, and number of texture rows over the height
+/// of the screen.
+const ANGLE_TO_SKY_SHIFT: u32 = 22;
+const SKY_TEXTURE_MID: Fixed = Fixed::from_int(100);
+const SKY_HEIGHT: i32 = 200;
+/// Things closer than this to the view plane are not drawn.
+const MIN_Z: Fixed = Fixed::from_int(4);
+/// Wall heights are stepped down the columns with 4 fewer fraction bits so
+/// that they can be multiplied by scales without overflowing.
+const HEIGHT_BITS: u32 = 12;
+const HEIGHT_UNIT: i32 = 1 << HEIGHT_BITS;
+/// Shift from a 16.16 slope to an index into `TAN_TO_ANGLE`.
+const SLOPE_SHIFT: u32 = FRAC_BITS - SLOPE_RANGE.trailing_zeros();
+
+/// Silhouette bits of a drawseg: sides of the wall that hide sprites behind it.
+const SIL_BOTTOM: u8 = 1;
+const SIL_TOP: u8 = 2;
+/// Masked column that has been drawn already.
+const MASKED_DRAWN: i32 = i32::MAX;
+
+/// Screen columns `first..=last` already covered by solid walls.
+#[derive(Clone, Copy)]
+struct ClipRange {
+    first: i32,
+    last: i32,
+}
+
+/// The seg being drawn by `store_wall_range`.
+struct WallSeg<'a> {
+    seg: &'a Seg,
+    /// Angle from the viewer to the start vertex.
+    rw_angle1: Angle,
+}
+
+/// Columns `x1..=x2` of a seg as drawn by `store_wall_range`, kept to clip
+/// sprites and to draw masked middle textures after the opaque pass.
+struct DrawSeg<'a> {
+    seg: &'a Seg,
+    x1: i32,
+    x2: i32,
+    scale1: Fixed,
+    scale2: Fixed,
+    scale_step: Fixed,
+    silhouette: u8,
+    /// Sprites standing at or above this height are not hidden by the bottom silhouette.
+    bottom_sil_height: Fixed,
+    /// Sprites reaching no higher than this are not hidden by the top silhouette.
+    top_sil_height: Fixed,
+    /// Ceiling and floor clips over `x1..=x2` after the seg was drawn.
+    sprite_top_clip: Option<Vec<i32>>,
+    sprite_bottom_clip: Option<Vec<i32>>,
+    /// Texture column of the middle texture over `x1..=x2`, for two-sided lines that have one.
+    masked_columns: Option<Vec<i32>>,
+}
+
+/// A thing projected to the screen.
+struct VisSprite {
+    x1: i32,
+    x2: i32,
+    /// Map position of the thing, and heights of its bottom and top.
+    gx: Fixed,
+    gy: Fixed,
+    gz: Fixed,
+    gzt: Fixed,
+    scale: Fixed,
+    /// Picture column at `x1`, and picture columns per screen column,
+    /// negative for flipped pictures.
+    start_frac: Fixed,
+    x_iscale: Fixed,
+    texture_mid: Fixed,
+    picture: usize,
+    colormap: usize,
+}
+
+/// Renders the first-person view of one frame into a palette-indexed screen.
+pub struct ViewRenderer<'a> {
+    pub wad_data: &'a WadData,
+    pub player: &'a Player,
+    screen: FrameBuffer<'a>,
+    width: i32,
+    height: i32,
+    center_x: Fixed,
+    center_y: i32,
+    /// Distance from the eye to the projection plane, in pixels.
+    projection: Fixed,
+    /// First column right of each view angle from -90 to 90 degrees, per fine angle.
+    view_angle_to_x: Vec<i32>,
+    /// View angle of the left edge of each column, positive to the left.
+    x_to_view_angle: Vec<Angle>,
+    /// View angle of the left edge of the screen.
+    clip_angle: Angle,
+    view_x: Fixed,
+    view_y: Fixed,
+    view_z: Fixed,
+    view_angle: Angle,
+    /// Slope from a row distance to a map distance, per row, and from a map
+    /// distance along the view direction to the distance to each column.
+    y_slope: Vec<Fixed>,
+    dist_scale: Vec<Fixed>,
+    /// Map step along the rows of a plane per unit of distance.
+    base_x_scale: Fixed,
+    base_y_scale: Fixed,
+    solid_segs: Vec<ClipRange>,
+    /// Last row above which nothing can be drawn, per column.
+    ceiling_clip: Vec<i32>,
+    /// First row below which nothing can be drawn, per column.
+    floor_clip: Vec<i32>,
+    visplanes: Visplanes,
+    /// Planes of the floor and ceiling of the subsector being drawn, if visible.
+    floor_plane: Option<usize>,
+    ceiling_plane: Option<usize>,
+    /// Column where the span being built on each row started.
+    span_start: Vec<i32>,
+    lights: Lights,
+    /// Light table row of the plane being drawn.
+    plane_light: usize,
+    draw_segs: Vec<DrawSeg<'a>>,
+    vis_sprites: Vec<VisSprite>,
+    /// Things standing in each sector, and whether they were projected this frame.
+    sector_things: Vec<Vec<&'a Thing>>,
+    sector_sprites_added: Vec<bool>,
+    /// Linedefs with walls drawn this frame, for the automap.
+    pub seen_lines: Vec<bool>,
+    /// Steps of the BSP traversal, recorded when set, for the BSP debug overlay.
+    pub bsp_trace: Option<Vec<BspStep>>,
+}
+
+impl<'a> ViewRenderer<'a> {
+    pub fn new(
+        wad_data: &'a WadData,
+        player: &'a Player,
+        things: &'a [Thing],
+        bsp: &BSP,
+        screen: FrameBuffer<'a>,
+    ) -> Self {
+        debug_assert_eq!(screen.format, PixelFormat::Indexed8);
+        let width = screen.width as i32;
+        let height = screen.height as i32;
+        let center_x = Fixed::from_int(width / 2);
+        let center_y = height / 2;
+        let (view_angle_to_x, x_to_view_angle) = texture_mapping(width, center_x);
+
+        // The vertical projection is the horizontal one: pixels are square.
+        let y_slope = (0..height)
+            .map(|y| center_x / Fixed(((y - center_y) << FRAC_BITS) + FRAC_UNIT / 2).abs())
+            .collect();
+        let dist_scale = x_to_view_angle
+            .iter()
+            .map(|angle| Fixed(FRAC_UNIT) / angle.cos().abs())
+            .collect();
+        let left_angle = player.angle - Angle(ANG90);
+
+        let sub_sector_id = bsp.point_in_sub_sector(&wad_data.nodes, player.pos.0, player.pos.1);
+        let mut sector_things = vec![Vec::new(); wad_data.sectors.len()];
+        for thing in things.iter() {
+            let sub_sector_id = bsp.point_in_sub_sector(&wad_data.nodes, thing.pos.0.into(), thing.pos.1.into());
+            if let Some(things) = wad_data.sub_sector_sector_id(sub_sector_id).and_then(|id| sector_things.get_mut(id)) {
+                things.push(thing);
+            }
+        }
+
+        let floor_height = wad_data
+            .sub_sector_sector(sub_sector_id)
+            .map_or(Fixed::ZERO, |s| s.floor_height.into());
+
+        ViewRenderer {
+            wad_data,
+            player,
+            screen,
+            width,
+            height,
+            center_x,
+            center_y,
+            projection: center_x,
+            clip_angle: x_to_view_angle[0],
+            view_angle_to_x,
+            x_to_view_angle,
+            view_x: player.pos.0,
+            view_y: player.pos.1,
+            view_z: floor_height + VIEW_HEIGHT,
+            view_angle: player.angle,
+            y_slope,
+            dist_scale,
+            base_x_scale: left_angle.cos() / center_x,
+            base_y_scale: -(left_angle.sin() / center_x),
+            solid_segs: vec![
+                ClipRange { first: i32::MIN, last: -1 },
+                ClipRange { first: width, last: i32::MAX },
+            ],
+            ceiling_clip: vec![-1; width as usize],
+            floor_clip: vec![height; width as usize],
+            visplanes: Visplanes::new(width),
+            floor_plane: None,
+            ceiling_plane: None,
+            span_start: vec![0; height as usize],
+            lights: Lights::new(width),
+            plane_light: 0,
+            draw_segs: Vec::new(),
+            vis_sprites: Vec::new(),
+            sector_sprites_added: vec![false; sector_things.len()],
+            sector_things,
+            seen_lines: vec![false; wad_data.linedefs.len()],
+            bsp_trace: None,
+        }
+    }
+
+    /// Draws walls while traversing the BSP front to back, then floors and ceilings,
+    /// then sprites and masked middle textures back to front.
+    pub fn render(&mut self, bsp: &BSP) {
+        bsp.render(self);
+        self.draw_planes();
+        self.draw_masked();
+    }
+
+    pub fn render_sub_sector(&mut self, sub_sector_id: u16) {
+        let wad_data = self.wad_data;
+        let sub_sector = &wad_data.sub_sectors[sub_sector_id as usize];
+        if let Some(sector) = wad_data.sub_sector_sector(sub_sector_id) {
+            self.floor_plane = if Fixed::from(sector.floor_height) < self.view_z {
+                Some(self.find_plane(sector.floor_height, &sector.floor_texture, sector.light_level))
+            } else {
+                None
+            };
+            // The sky is visible even through ceilings below the eye.
+            self.ceiling_plane = if Fixed::from(sector.ceiling_height) > self.view_z || wad_data.is_sky(&sector.ceiling_texture) {
+                Some(self.find_plane(sector.ceiling_height, &sector.ceiling_texture, sector.light_level))
+            } else {
+                None
+            };
+        } else {
+            // Without a sector the segs must not draw into the planes of the
+            // previous subsector.
+            self.floor_plane = None;
+            self.ceiling_plane = None;
+        }
+        if let Some(sector_id) = wad_data.sub_sector_sector_id(sub_sector_id) {
+            self.add_sprites(sector_id);
+        }
+        let first = sub_sector.first_seg_id as usize;
+        let mut drawn_segs = Vec::new();
+        for (i, seg) in wad_data.segments[first..first + sub_sector.seg_count as usize].iter().enumerate() {
+            let draw_seg_count = self.draw_segs.len();
+            self.add_line(seg);
+            if self.bsp_trace.is_some() && self.draw_segs.len() > draw_seg_count {
+                drawn_segs.push((first + i) as u16);
+            }
+        }
+        self.trace(BspStep::SubSector { sub_sector_id, drawn_segs });
+    }
+
+    /// Records a step of the BSP traversal if a trace was requested.
+    pub fn trace(&mut self, step: BspStep) {
+        if let Some(trace) = &mut self.bsp_trace {
+            trace.push(step);
+        }
+    }
+
+    /// Whether every column of the screen is covered by solid walls.
+    pub fn is_screen_full(&self) -> bool {
+        self.solid_segs[0].last >= self.width - 1
+    }
+
+    /// Whether part of a bounding box may be visible: it lies at least partly
+    /// in the field of view and not entirely behind solid walls.
+    pub fn check_bbox(&self, bbox: &BBox) -> bool {
+        let (top, bottom) = (Fixed::from(bbox.top), Fixed::from(bbox.bottom));
+        let (left, right) = (Fixed::from(bbox.left), Fixed::from(bbox.right));
+
+        // Position of the viewer relative to the box, in a 3x3 grid.
+        let box_x = if self.view_x <= left {
+            0
+        } else if self.view_x < right {
+            1
+        } else {
+            2
+        };
+        let box_y = if self.view_y >= top {
+            0
+        } else if self.view_y > bottom {
+            1
+        } else {
+            2
+        };
+
+        // The two corners that bound the box as seen from the viewer.
+        let ((x1, y1), (x2, y2)) = match (box_y, box_x) {
+            (0, 0) => ((right, top), (left, bottom)),
+            (0, 1) => ((right, top), (left, top)),
+            (0, 2) => ((right, bottom), (left, top)),
+            (1, 0) => ((left, top), (left, bottom)),
+            (1, 1) => return true,
+            (1, 2) => ((right, bottom), (right, top)),
+            (2, 0) => ((left, top), (right, bottom)),
+            (2, 1) => ((left, bottom), (right, bottom)),
+            _ => ((left, bottom), (right, top)),
+        };
+
+        let mut angle1 = self.point_to_angle(x1, y1) - self.view_angle;
+        let mut angle2 = self.point_to_angle(x2, y2) - self.view_angle;
+        let span = angle1 - angle2;
+        if span.0 >= ANG180 {
+            return true;
+        }
+
+        let fov = 2 * self.clip_angle.0;
+        let tspan = (angle1 + self.clip_angle).0;
+        if tspan > fov {
+            if tspan - fov >= span.0 {
+                return false;
+            }
+            angle1 = self.clip_angle;
+        }
+        let tspan = (self.clip_angle - angle2).0;
+        if tspan > fov {
+            if tspan - fov >= span.0 {
+                return false;
+            }
+            angle2 = -self.clip_angle;
+        }
+
+        let sx1 = self.angle_to_x(angle1);
+        let sx2 = self.angle_to_x(angle2);
+        if sx1 >= sx2 {
+            return false;
+        }
+        let sx2 = sx2 - 1;
+
+        // Visible unless the columns lie within a single solid range.
+        let start = self.solid_segs.iter().find(|range| range.last >= sx2);
+        !matches!(start, Some(range) if sx1 >= range.first && sx2 <= range.last)
+    }
+
+    /// Visplane for a floor or ceiling. All sky planes are merged into one, as
+    /// the sky does not depend on height or light.
+    fn find_plane(&mut self, height: i16, flat: &[u8; 8], light_level: i16) -> usize {
+        let flat_id = self.wad_data.flats.id(flat);
+        if self.wad_data.is_sky(flat) {
+            self.visplanes.find(0, flat_id, 0)
+        } else {
+            self.visplanes.find(height as i32, flat_id, light_level)
+        }
+    }
+
+    /// Clips a seg to the field of view and hands its visible columns to the
+    /// solid-seg clipper.
+    fn add_line(&mut self, seg: &'a Seg) {
+        let v1 = &self.wad_data.vertexes[seg.start_vertex_id as usize];
+        let v2 = &self.wad_data.vertexes[seg.end_vertex_id as usize];
+        let angle1 = self.point_to_angle(v1.x.into(), v1.y.into());
+        let angle2 = self.point_to_angle(v2.x.into(), v2.y.into());
+
+        // Back-facing segs span more than half a turn.
+        let span = angle1 - angle2;
+        if span.0 >= ANG180 {
+            return;
+        }
+        let rw_angle1 = angle1;
+
+        let mut angle1 = angle1 - self.view_angle;
+        let mut angle2 = angle2 - self.view_angle;
+
+        let fov = 2 * self.clip_angle.0;
+        let tspan = (angle1 + self.clip_angle).0;
+        if tspan > fov {
+            if tspan - fov >= span.0 {
+                return;
+            }
+            angle1 = self.clip_angle;
+        }
+        let tspan = (self.clip_angle - angle2).0;
+        if tspan > fov {
+            if tspan - fov >= span.0 {
+                return;
+            }
+            angle2 = -self.clip_angle;
+        }
+
+        let x1 = self.angle_to_x(angle1);
+        let x2 = self.angle_to_x(angle2);
+        if x1 >= x2 {
+            return;
+        }
+
+        let wall = WallSeg { seg, rw_angle1 };
+        let (front_sector, back_sector) = match (self.wad_data.seg_front_sector(seg), self.wad_data.seg_back_sector(seg)) {
+            (Some(front_sector), Some(back_sector)) => (front_sector, back_sector),
+            _ => {
+                self.clip_solid_wall_segment(x1, x2 - 1, &wall);
+                return;
+            }
+        };
+
+        // Closed doors block the view like one-sided walls.
+        if back_sector.ceiling_height <= front_sector.floor_height
+            || back_sector.floor_height >= front_sector.ceiling_height
+        {
+            self.clip_solid_wall_segment(x1, x2 - 1, &wall);
+            return;
+        }
+
+        // Lines with nothing to draw on either side, e.g. trigger lines.
+        if back_sector.ceiling_height == front_sector.ceiling_height
+            && back_sector.floor_height == front_sector.floor_height
+            && back_sector.ceiling_texture == front_sector.ceiling_texture
+            && back_sector.floor_texture == front_sector.floor_texture
+            && back_sector.light_level == front_sector.light_level
+            && self.wad_data.textures.id(&self.wad_data.seg_sidedef(seg).map_or([0; 8], |s| s.middle_texture)).is_none()
+        {
+            return;
+        }
+
+        self.clip_pass_wall_segment(x1, x2 - 1, &wall);
+    }
+
+    /// Draws the parts of columns `first..=last` not yet covered by solid walls
+    /// and marks them as covered.
+    fn clip_solid_wall_segment(&mut self, first: i32, last: i32, wall: &WallSeg<'a>) {
+        let mut start = 0;
+        while self.solid_segs[start].last < first - 1 {
+            start += 1;
+        }
+
+        if first < self.solid_segs[start].first {
+            if last < self.solid_segs[start].first - 1 {
+                // The seg is entirely visible: insert a new clip range.
+                self.store_wall_range(first, last, wall);
+                self.solid_segs.insert(start, ClipRange { first, last });
+                return;
+            }
+            // There is a fragment above `start`.
+            self.store_wall_range(first, self.solid_segs[start].first - 1, wall);
+            self.solid_segs[start].first = first;
+        }
+
+        // Bottom contained in `start`?
+        if last <= self.solid_segs[start].last {
+            return;
+        }
+
+        let mut next = start;
+        while last >= self.solid_segs[next + 1].first - 1 {
+            // There is a fragment between two ranges.
+            self.store_wall_range(self.solid_segs[next].last + 1, self.solid_segs[next + 1].first - 1, wall);
+            next += 1;
+            if last <= self.solid_segs[next].last {
+                // Bottom is contained in `next`: adjust the clip range.
+                self.solid_segs[start].last = self.solid_segs[next].last;
+                self.solid_segs.drain(start + 1..=next);
+                return;
+            }
+        }
+
+        // There is a fragment after `next`.
+        self.store_wall_range(self.solid_segs[next].last + 1, last, wall);
+        self.solid_segs[start].last = last;
+        self.solid_segs.drain(start + 1..=next);
+    }
+
+    /// Draws the parts of columns `first..=last` not yet covered by solid walls,
+    /// leaving them open for what lies behind.
+    fn clip_pass_wall_segment(&mut self, first: i32, last: i32, wall: &WallSeg<'a>) {
+        let mut start = 0;
+        while self.solid_segs[start].last < first - 1 {
+            start += 1;
+        }
+
+        if first < self.solid_segs[start].first {
+            if last < self.solid_segs[start].first - 1 {
+                self.store_wall_range(first, last, wall);
+                return;
+            }
+            self.store_wall_range(first, self.solid_segs[start].first - 1, wall);
+        }
+
+        if last <= self.solid_segs[start].last {
+            return;
+        }
+
+        while last >= self.solid_segs[start + 1].first - 1 {
+            self.store_wall_range(self.solid_segs[start].last + 1, self.solid_segs[start + 1].first - 1, wall);
+            start += 1;
+            if last <= self.solid_segs[start].last {
+                return;
+            }
+        }
+
+        self.store_wall_range(self.solid_segs[start].last + 1, last, wall);
+    }
+
+    /// Draws columns `x1..=x2` of a wall: the middle texture of one-sided lines,
+    /// or the upper and lower textures of two-sided lines, narrowing the clip
+    /// windows of the columns in both cases.
+    fn store_wall_range(&mut self, x1: i32, x2: i32, wall: &WallSeg<'a>) {
+        let wad_data = self.wad_data;
+        let seg = wall.seg;
+        let linedef = &wad_data.linedefs[seg.linedef_id as usize];
+        let (sidedef, front_sector) = match (wad_data.seg_sidedef(seg), wad_data.seg_front_sector(seg)) {
+            (Some(sidedef), Some(sector)) => (sidedef, sector),
+            _ => return,
+        };
+        let back_sector = wad_data.seg_back_sector(seg);
+        self.seen_lines[seg.linedef_id as usize] = true;
+
+        // Distance from the viewer to the line the seg lies on.
+        let rw_normal_angle = Angle::from_seg_angle(seg.angle) + Angle(ANG90);
+        let normal_offset = rw_normal_angle - wall.rw_angle1;
+        let offset_angle = Angle((normal_offset.0 as i32).unsigned_abs().min(ANG90));
+        let v1 = &wad_data.vertexes[seg.start_vertex_id as usize];
+        let hyp = self.point_to_dist(v1.x.into(), v1.y.into());
+        let rw_distance = hyp * (Angle(ANG90) - offset_angle).sin();
+
+        let scale1 = self.scale_from_global_angle(self.view_angle + self.x_to_view_angle[x1 as usize], rw_normal_angle, rw_distance);
+        let (scale2, scale_step) = if x2 > x1 {
+            let scale2 = self.scale_from_global_angle(self.view_angle + self.x_to_view_angle[x2 as usize], rw_normal_angle, rw_distance);
+            (scale2, Fixed((scale2.0 - scale1.0) / (x2 - x1)))
+        } else {
+            (scale1, Fixed::ZERO)
+        };
+        let mut scale = scale1;
+
+        let mut draw_seg = DrawSeg {
+            seg,
+            x1,
+            x2,
+            scale1,
+            scale2,
+            scale_step,
+            silhouette: 0,
+            bottom_sil_height: Fixed::MAX,
+            top_sil_height: Fixed::MIN,
+            sprite_top_clip: None,
+            sprite_bottom_clip: None,
+            masked_columns: None,
+        };
+        let columns = (x2 - x1 + 1) as usize;
+
+        let mut world_top = Fixed::from(front_sector.ceiling_height) - self.view_z;
+        let world_bottom = Fixed::from(front_sector.floor_height) - self.view_z;
+        let row_offset = Fixed::from(sidedef.y_offset);
+
+        let mut mid_texture = None;
+        let mut top_texture = None;
+        let mut bottom_texture = None;
+        let mut mid_texture_mid = Fixed::ZERO;
+        let mut top_texture_mid = Fixed::ZERO;
+        let mut bottom_texture_mid = Fixed::ZERO;
+        let mut world_high = world_top;
+        let mut world_low = world_bottom;
+        let mut mark_floor = true;
+        let mut mark_ceiling = true;
+
+        match back_sector {
+            None => {
+                // One-sided line: a single texture from floor to ceiling.
+                mid_texture = wad_data.textures.get(&sidedef.middle_texture);
+                mid_texture_mid = match mid_texture {
+                    Some(texture) if linedef.flags & ML_DONTPEGBOTTOM != 0 => {
+                        Fixed::from(front_sector.floor_height) + Fixed::from_int(texture.height as i32) - self.view_z
+                    }
+                    _ => world_top,
+                };
+                mid_texture_mid += row_offset;
+
+                draw_seg.silhouette = SIL_BOTTOM | SIL_TOP;
+                draw_seg.sprite_top_clip = Some(vec![self.height; columns]);
+                draw_seg.sprite_bottom_clip = Some(vec![-1; columns]);
+            }
+            Some(back_sector) => {
+                if front_sector.floor_height > back_sector.floor_height {
+                    draw_seg.silhouette = SIL_BOTTOM;
+                    draw_seg.bottom_sil_height = front_sector.floor_height.into();
+                } else if Fixed::from(back_sector.floor_height) > self.view_z {
+                    draw_seg.silhouette = SIL_BOTTOM;
+                }
+                if front_sector.ceiling_height < back_sector.ceiling_height {
+                    draw_seg.silhouette |= SIL_TOP;
+                    draw_seg.top_sil_height = front_sector.ceiling_height.into();
+                } else if Fixed::from(back_sector.ceiling_height) < self.view_z {
+                    draw_seg.silhouette |= SIL_TOP;
+                }
+                if back_sector.ceiling_height <= front_sector.floor_height {
+                    draw_seg.silhouette |= SIL_BOTTOM;
+                    draw_seg.bottom_sil_height = Fixed::MIN;
+                    draw_seg.sprite_bottom_clip = Some(vec![-1; columns]);
+                }
+                if back_sector.floor_height >= front_sector.ceiling_height {
+                    draw_seg.silhouette |= SIL_TOP;
+                    draw_seg.top_sil_height = Fixed::MAX;
+                    draw_seg.sprite_top_clip = Some(vec![self.height; columns]);
+                }
+                if wad_data.textures.id(&sidedef.middle_texture).is_some() {
+                    draw_seg.masked_columns = Some(vec![0; columns]);
+                }
+
+                world_high = Fixed::from(back_sector.ceiling_height) - self.view_z;
+                world_low = Fixed::from(back_sector.floor_height) - self.view_z;
+
+                // No upper wall between two outdoor sectors: the sky shows instead.
+                if wad_data.is_sky(&front_sector.ceiling_texture) && wad_data.is_sky(&back_sector.ceiling_texture) {
+                    world_top = world_high;
+                }
+
+                // Planes continue through the line unless something about them changes.
+                mark_floor = world_low != world_bottom
+                    || back_sector.floor_texture != front_sector.floor_texture
+                    || back_sector.light_level != front_sector.light_level;
+                mark_ceiling = world_high != world_top
+                    || back_sector.ceiling_texture != front_sector.ceiling_texture
+                    || back_sector.light_level != front_sector.light_level;
+
+                if back_sector.ceiling_height <= front_sector.floor_height
+                    || back_sector.floor_height >= front_sector.ceiling_height
+                {
+                    // Closed door.
+                    mark_floor = true;
+                    mark_ceiling = true;
+                }
+
+                if world_high < world_top {
+                    top_texture = wad_data.textures.get(&sidedef.upper_texture);
+                    top_texture_mid = match top_texture {
+                        // Unpegged upper textures hang from the front ceiling,
+                        // pegged ones rest on the back ceiling.
+                        Some(texture) if linedef.flags & ML_DONTPEGTOP == 0 => {
+                            Fixed::from(back_sector.ceiling_height) + Fixed::from_int(texture.height as i32) - self.view_z
+                        }
+                        _ => world_top,
+                    };
+                    top_texture_mid += row_offset;
+                }
+                if world_low > world_bottom {
+                    bottom_texture = wad_data.textures.get(&sidedef.lower_texture);
+                    // Unpegged lower textures are aligned with the front ceiling.
+                    bottom_texture_mid = if linedef.flags & ML_DONTPEGBOTTOM != 0 {
+                        world_top
+                    } else {
+                        world_low
+                    };
+                    bottom_texture_mid += row_offset;
+                }
+            }
+        }
+
+        let wall_light = self.wall_light(seg, front_sector);
+
+        // Texture column at the foot of the perpendicular from the viewer.
+        let mut rw_offset = hyp * offset_angle.sin();
+        if normal_offset.0 < ANG180 {
+            rw_offset = -rw_offset;
+        }
+        rw_offset += Fixed::from(sidedef.x_offset) + Fixed::from(seg.offset);
+        let rw_center_angle = self.view_angle - rw_normal_angle;
+
+        // Floors above and ceilings below the eye are not visible.
+        if Fixed::from(front_sector.floor_height) >= self.view_z {
+            mark_floor = false;
+        }
+        if Fixed::from(front_sector.ceiling_height) <= self.view_z && !wad_data.is_sky(&front_sector.ceiling_texture) {
+            mark_ceiling = false;
+        }
+        let ceiling_plane = match self.ceiling_plane {
+            Some(plane) if mark_ceiling => Some(self.visplanes.check(plane, x1, x2)),
+            _ => None,
+        };
+        let floor_plane = match self.floor_plane {
+            Some(plane) if mark_floor => Some(self.visplanes.check(plane, x1, x2)),
+            _ => None,
+        };
+        self.ceiling_plane = ceiling_plane.or(self.ceiling_plane);
+        self.floor_plane = floor_plane.or(self.floor_plane);
+
+        // Screen rows of the wall edges, stepped along the columns.
+        let center_y = Fixed::from_int(self.center_y).0 >> 4;
+        let edge = |height: Fixed| {
+            let height = Fixed(height.0 >> 4);
+            (Fixed(center_y) - height * scale1, -(scale_step * height))
+        };
+        let (mut top_frac, top_step) = edge(world_top);
+        let (mut bottom_frac, bottom_step) = edge(world_bottom);
+        let (mut pix_high, pix_high_step) = edge(world_high);
+        let (mut pix_low, pix_low_step) = edge(world_low);
+
+        for x in x1..=x2 {
+            let xi = x as usize;
+            let yl = ((top_frac.0 + HEIGHT_UNIT - 1) >> HEIGHT_BITS).max(self.ceiling_clip[xi] + 1);
+            if let Some(plane) = ceiling_plane {
+                let top = self.ceiling_clip[xi] + 1;
+                let bottom = (yl - 1).min(self.floor_clip[xi] - 1);
+                if top <= bottom {
+                    self.visplanes.planes[plane].set_span(x, top, bottom);
+                }
+            }
+
+            let yh = (bottom_frac.0 >> HEIGHT_BITS).min(self.floor_clip[xi] - 1);
+            if let Some(plane) = floor_plane {
+                let top = (yh + 1).max(self.ceiling_clip[xi] + 1);
+                let bottom = self.floor_clip[xi] - 1;
+                if top <= bottom {
+                    self.visplanes.planes[plane].set_span(x, top, bottom);
+                }
+            }
+
+            let angle = rw_center_angle + self.x_to_view_angle[xi];
+            let texture_column = (rw_offset - angle.tan() * rw_distance).0 >> FRAC_BITS;
+            let iscale = inverse_scale(scale);
+            let colormap = &wad_data.colormaps[self.lights.scale_colormap(wall_light, scale)];
+            if let Some(masked_columns) = draw_seg.masked_columns.as_mut() {
+                masked_columns[(x - x1) as usize] = texture_column;
+            }
+
+            if back_sector.is_none() {
+                if let Some(texture) = mid_texture {
+                    self.draw_column(x, yl, yh, texture, texture_column, mid_texture_mid, iscale, colormap);
+                }
+                self.ceiling_clip[xi] = self.height;
+                self.floor_clip[xi] = -1;
+            } else {
+                // As in the original game, the view is clipped to the upper
+                // and lower walls only where they have a texture.
+                if let Some(texture) = top_texture {
+                    let mid = (pix_high.0 >> HEIGHT_BITS).min(self.floor_clip[xi] - 1);
+                    if mid >= yl {
+                        self.draw_column(x, yl, mid, texture, texture_column, top_texture_mid, iscale, colormap);
+                        self.ceiling_clip[xi] = mid;
+                    } else {
+                        self.ceiling_clip[xi] = yl - 1;
+                    }
+                } else if mark_ceiling {
+                    self.ceiling_clip[xi] = yl - 1;
+                }
+
+                if let Some(texture) = bottom_texture {
+                    let mid = ((pix_low.0 + HEIGHT_UNIT - 1) >> HEIGHT_BITS).max(self.ceiling_clip[xi] + 1);
+                    if mid <= yh {
+                        self.draw_column(x, mid, yh, texture, texture_column, bottom_texture_mid, iscale, colormap);
+                        self.floor_clip[xi] = mid;
+                    } else {
+                        self.floor_clip[xi] = yh + 1;
+                    }
+                } else if mark_floor {
+                    self.floor_clip[xi] = yh + 1;
+                }
+            }
+            scale += scale_step;
+            top_frac += top_step;
+            bottom_frac += bottom_step;
+            pix_high += pix_high_step;
+            pix_low += pix_low_step;
+        }
+
+        // Save the clips for sprites behind the seg and for its masked texture.
+        let masked = draw_seg.masked_columns.is_some();
+        let range = x1 as usize..=x2 as usize;
+        if (draw_seg.silhouette & SIL_TOP != 0 || masked) && draw_seg.sprite_top_clip.is_none() {
+            draw_seg.sprite_top_clip = Some(self.ceiling_clip[range.clone()].to_vec());
+        }
+        if (draw_seg.silhouette & SIL_BOTTOM != 0 || masked) && draw_seg.sprite_bottom_clip.is_none() {
+            draw_seg.sprite_bottom_clip = Some(self.floor_clip[range].to_vec());
+        }
+        if masked && draw_seg.silhouette & SIL_TOP == 0 {
+            draw_seg.silhouette |= SIL_TOP;
+            draw_seg.top_sil_height = Fixed::MIN;
+        }
+        if masked && draw_seg.silhouette & SIL_BOTTOM == 0 {
+            draw_seg.silhouette |= SIL_BOTTOM;
+            draw_seg.bottom_sil_height = Fixed::MAX;
+        }
+        self.draw_segs.push(draw_seg);
+    }
+
+    /// Projects the things of a sector, once per frame.
+    fn add_sprites(&mut self, sector_id: usize) {
+        if self.sector_sprites_added[sector_id] {
+            return;
+        }
+        self.sector_sprites_added[sector_id] = true;
+        let sector = &self.wad_data.sectors[sector_id];
+        let light = Lights::light_index(sector.light_level, 0);
+        for i in 0..self.sector_things[sector_id].len() {
+            let thing = self.sector_things[sector_id][i];
+            self.project_sprite(thing, sector.floor_height.into(), light);
+        }
+    }
+
+    /// Adds a thing standing at height `z` in a sector with the given light
+    /// to the sprites to draw, if it is in view.
+    fn project_sprite(&mut self, thing: &Thing, z: Fixed, light: usize) {
+        let gx = Fixed::from(thing.pos.0);
+        let gy = Fixed::from(thing.pos.1);
+        let tr_x = gx - self.view_x;
+        let tr_y = gy - self.view_y;
+        let (sin, cos) = (self.view_angle.sin(), self.view_angle.cos());
+
+        // Distance along the view direction, and offset to the right of it.
+        let tz = tr_x * cos + tr_y * sin;
+        if tz < MIN_Z {
+            return;
+        }
+        let x_scale = self.projection / tz;
+        let mut tx = tr_x * sin - tr_y * cos;
+        if tx.abs() > Fixed(tz.0.saturating_mul(4)) {
+            return;
+        }
+
+        let sprites = &self.wad_data.sprites;
+        let frame = match thing_sprite(thing.ttype).and_then(|(sprite, frame)| sprites.frame(sprite, frame)) {
+            Some(frame) => frame,
+            None => return,
+        };
+        let rotation = if frame.rotate {
+            // Eight views, each centered on its direction.
+            let angle = self.point_to_angle(gx, gy) - Angle::from_map_angle(thing.angle) + Angle(ANG45 / 2 * 9);
+            (angle.0 >> 29) as usize
+        } else {
+            0
+        };
+        let picture_id = match frame.pictures[rotation] {
+            Some(picture_id) => picture_id,
+            None => return,
+        };
+        let picture = &sprites.pictures[picture_id];
+
+        tx -= Fixed::from(picture.left_offset);
+        let x1 = (self.center_x + tx * x_scale).0 >> FRAC_BITS;
+        if x1 >= self.width {
+            return;
+        }
+        let picture_width = Fixed::from_int(picture.width as i32);
+        tx += picture_width;
+        let x2 = ((self.center_x + tx * x_scale).0 >> FRAC_BITS) - 1;
+        if x2 < 0 || x2 < x1 {
+            return;
+        }
+
+        let iscale = Fixed(FRAC_UNIT) / x_scale;
+        let (mut start_frac, x_iscale) = if frame.flip[rotation] {
+            (picture_width - Fixed(1), -iscale)
+        } else {
+            (Fixed::ZERO, iscale)
+        };
+        if x1 < 0 {
+            start_frac += Fixed(x_iscale.0.wrapping_mul(-x1));
+        }
+
+        let gzt = z + Fixed::from(picture.top_offset);
+        self.vis_sprites.push(VisSprite {
+            x1: x1.max(0),
+            x2: x2.min(self.width - 1),
+            gx,
+            gy,
+            gz: z,
+            gzt,
+            scale: x_scale,
+            start_frac,
+            x_iscale,
+            texture_mid: gzt - self.view_z,
+            picture: picture_id,
+            colormap: self.lights.scale_colormap(light, x_scale),
+        });
+    }
+
+    /// Draws sprites from the farthest to the nearest, interleaved with the masked
+    /// middle textures behind them, then the remaining masked textures.
+    fn draw_masked(&mut self) {
+        let mut draw_segs = std::mem::take(&mut self.draw_segs);
+        let mut vis_sprites = std::mem::take(&mut self.vis_sprites);
+        vis_sprites.sort_by_key(|sprite| sprite.scale);
+
+        for sprite in vis_sprites.iter() {
+            self.draw_sprite(sprite, &mut draw_segs);
+        }
+        for draw_seg in draw_segs.iter_mut().rev() {
+            if draw_seg.masked_columns.is_some() {
+                let (x1, x2) = (draw_seg.x1, draw_seg.x2);
+                self.render_masked_seg_range(draw_seg, x1, x2);
+            }
+        }
+    }
+
+    /// Draws a sprite clipped by the silhouettes of the walls in front of it.
+    fn draw_sprite(&mut self, sprite: &VisSprite, draw_segs: &mut [DrawSeg]) {
+        let columns = (sprite.x2 - sprite.x1 + 1) as usize;
+        let mut clip_top = vec![-2; columns];
+        let mut clip_bottom = vec![-2; columns];
+
+        // Walls drawn later are nearer, so the first clip found per column wins.
+        for draw_seg in draw_segs.iter_mut().rev() {
+            if draw_seg.x1 > sprite.x2
+                || draw_seg.x2 < sprite.x1
+                || (draw_seg.silhouette == 0 && draw_seg.masked_columns.is_none())
+            {
+                continue;
+            }
+            let r1 = draw_seg.x1.max(sprite.x1);
+            let r2 = draw_seg.x2.min(sprite.x2);
+
+            let low_scale = draw_seg.scale1.min(draw_seg.scale2);
+            let scale = draw_seg.scale1.max(draw_seg.scale2);
+            if scale < sprite.scale
+                || (low_scale < sprite.scale && !self.point_on_seg_back_side(sprite.gx, sprite.gy, draw_seg.seg))
+            {
+                // The seg is behind the sprite: its masked texture goes first.
+                if draw_seg.masked_columns.is_some() {
+                    self.render_masked_seg_range(draw_seg, r1, r2);
+                }
+                continue;
+            }
+
+            let mut silhouette = draw_seg.silhouette;
+            if sprite.gz >= draw_seg.bottom_sil_height {
+                silhouette &= !SIL_BOTTOM;
+            }
+            if sprite.gzt <= draw_seg.top_sil_height {
+                silhouette &= !SIL_TOP;
+            }
+            for x in r1..=r2 {
+                let i = (x - sprite.x1) as usize;
+                let d = (x - draw_seg.x1) as usize;
+                if silhouette & SIL_BOTTOM != 0 && clip_bottom[i] == -2 {
+                    if let Some(clip) = &draw_seg.sprite_bottom_clip {
+                        clip_bottom[i] = clip[d];
+                    }
+                }
+                if silhouette & SIL_TOP != 0 && clip_top[i] == -2 {
+                    if let Some(clip) = &draw_seg.sprite_top_clip {
+                        clip_top[i] = clip[d];
+                    }
+                }
+            }
+        }
+
+        let picture = &self.wad_data.sprites.pictures[sprite.picture];
+        let colormap = &self.wad_data.colormaps[sprite.colormap];
+        let mut frac = sprite.start_frac;
+        for x in sprite.x1..=sprite.x2 {
+            let i = (x - sprite.x1) as usize;
+            let top = if clip_top[i] == -2 { -1 } else { clip_top[i] };
+            let bottom = if clip_bottom[i] == -2 { self.height } else { clip_bottom[i] };
+
+            let column = (frac.0 >> FRAC_BITS).clamp(0, picture.width as i32 - 1);
+            for post in picture.columns[column as usize].iter() {
+                self.draw_post(x, post.top_delta, &post.pixels, sprite.texture_mid, sprite.scale, top, bottom, colormap);
+            }
+            frac += sprite.x_iscale;
+        }
+    }
+
+    /// Draws the masked middle texture of a drawseg over columns `x1..=x2`,
+    /// skipping the columns already drawn.
+    fn render_masked_seg_range(&mut self, draw_seg: &mut DrawSeg, x1: i32, x2: i32) {
+        let wad_data = self.wad_data;
+        let seg = draw_seg.seg;
+        let linedef = &wad_data.linedefs[seg.linedef_id as usize];
+        let (sidedef, front_sector, back_sector) = match (
+            wad_data.seg_sidedef(seg),
+            wad_data.seg_front_sector(seg),
+            wad_data.seg_back_sector(seg),
+        ) {
+            (Some(sidedef), Some(front_sector), Some(back_sector)) => (sidedef, front_sector, back_sector),
+            _ => return,
+        };
+        let texture = match wad_data.textures.get(&sidedef.middle_texture) {
+            Some(texture) => texture,
+            None => return,
+        };
+        let (masked_columns, top_clip, bottom_clip) = match (
+            draw_seg.masked_columns.as_mut(),
+            draw_seg.sprite_top_clip.as_ref(),
+            draw_seg.sprite_bottom_clip.as_ref(),
+        ) {
+            (Some(columns), Some(top_clip), Some(bottom_clip)) => (columns, top_clip, bottom_clip),
+            _ => return,
+        };
+
+        // Masked textures are drawn once, not tiled, from the top of the opening
+        // or up from its bottom.
+        let mut texture_mid = if linedef.flags & ML_DONTPEGBOTTOM != 0 {
+            Fixed::from(front_sector.floor_height.max(back_sector.floor_height)) + Fixed::from_int(texture.height as i32) - self.view_z
+        } else {
+            Fixed::from(front_sector.ceiling_height.min(back_sector.ceiling_height)) - self.view_z
+        };
+        texture_mid += sidedef.y_offset.into();
+        let wall_light = self.wall_light(seg, front_sector);
+
+        let mut scale = draw_seg.scale1 + Fixed(draw_seg.scale_step.0.wrapping_mul(x1 - draw_seg.x1));
+        for x in x1..=x2 {
+            let d = (x - draw_seg.x1) as usize;
+            if masked_columns[d] != MASKED_DRAWN {
+                let colormap = &wad_data.colormaps[self.lights.scale_colormap(wall_light, scale)];
+                let pixels = texture.column(masked_columns[d]);
+                let mask = texture.column_mask(masked_columns[d]);
+                // Draw each run of opaque pixels as a post.
+                let mut row = 0;
+                while row < mask.len() {
+                    if !mask[row] {
+                        row += 1;
+                        continue;
+                    }
+                    let start = row;
+                    while row < mask.len() && mask[row] {
+                        row += 1;
+                    }
+                    self.draw_post(x, start, &pixels[start..row], texture_mid, scale, top_clip[d], bottom_clip[d], colormap);
+                }
+                masked_columns[d] = MASKED_DRAWN;
+            }
+            scale += draw_seg.scale_step;
+        }
+    }
+
+    /// Draws a run of picture pixels starting `top_delta` rows below the top of
+    /// the picture, between rows `clip_top` and `clip_bottom` exclusive.
+    #[allow(clippy::too_many_arguments)]
+    fn draw_post(
+        &mut self,
+        x: i32,
+        top_delta: usize,
+        pixels: &[u8],
+        texture_mid: Fixed,
+        scale: Fixed,
+        clip_top: i32,
+        clip_bottom: i32,
+        colormap: &Colormap,
+    ) {
+        if pixels.is_empty() {
+            return;
+        }
+        let texture_mid = texture_mid - Fixed::from_int(top_delta as i32);
+        let top = Fixed::from_int(self.center_y) - texture_mid * scale;
+        let bottom = top + Fixed(scale.0.wrapping_mul(pixels.len() as i32));
+        let yl = ((top.0 + FRAC_UNIT - 1) >> FRAC_BITS).max(clip_top + 1).max(0);
+        let yh = ((bottom.0 - 1) >> FRAC_BITS).min(clip_bottom - 1).min(self.height - 1);
+        let iscale = inverse_scale(scale);
+        let mut frac = texture_mid + Fixed(iscale.0.wrapping_mul(yl - self.center_y));
+        for y in yl..=yh {
+            let row = (frac.0 >> FRAC_BITS).clamp(0, pixels.len() as i32 - 1);
+            self.screen.pixels[y as usize * self.screen.pitch + x as usize] = colormap[pixels[row as usize] as usize];
+            frac += iscale;
+        }
+    }
+
+    /// Light table row of a wall: the sector light, one level darker for walls
+    /// along the x axis and one brighter along the y axis for some contrast.
+    fn wall_light(&self, seg: &Seg, sector: &Sector) -> usize {
+        let v1 = &self.wad_data.vertexes[seg.start_vertex_id as usize];
+        let v2 = &self.wad_data.vertexes[seg.end_vertex_id as usize];
+        let contrast = if v1.y == v2.y {
+            -1
+        } else if v1.x == v2.x {
+            1
+        } else {
+            0
+        };
+        Lights::light_index(sector.light_level, contrast)
+    }
+
+    /// Whether a map point lies behind a seg, like `R_PointOnSegSide`.
+    fn point_on_seg_back_side(&self, x: Fixed, y: Fixed, seg: &Seg) -> bool {
+        let v1 = &self.wad_data.vertexes[seg.start_vertex_id as usize];
+        let v2 = &self.wad_data.vertexes[seg.end_vertex_id as usize];
+        let (x1, y1) = (v1.x as i32, v1.y as i32);
+        BSP::point_on_line_back_side(x, y, (x1, y1), (v2.x as i32 - x1, v2.y as i32 - y1))
+    }
+
+    /// Draws rows `yl..=yh` of screen column `x` from a texture column.
+    #[allow(clippy::too_many_arguments)]
+    fn draw_column(
+        &mut self,
+        x: i32,
+        yl: i32,
+        yh: i32,
+        texture: &Texture,
+        column: i32,
+        texture_mid: Fixed,
+        iscale: Fixed,
+        colormap: &Colormap,
+    ) {
+        if yl > yh {
+            return;
+        }
+        let source = texture.column(column);
+        let height = texture.height as i32;
+        let mut frac = texture_mid + Fixed(iscale.0.wrapping_mul(yl - self.center_y));
+        for y in yl..=yh {
+            let row = (frac.0 >> FRAC_BITS).rem_euclid(height) as usize;
+            self.screen.pixels[y as usize * self.screen.pitch + x as usize] = colormap[source[row] as usize];
+            frac += iscale;
+        }
+    }
+
+    /// Turns the column spans of every visplane into rows and draws them.
+    fn draw_planes(&mut self) {
+        for plane_id in 0..self.visplanes.planes.len() {
+            let plane = &self.visplanes.planes[plane_id];
+            if plane.min_x > plane.max_x {
+                continue;
+            }
+            if plane.flat_id.is_some() && plane.flat_id == self.wad_data.sky_flat {
+                self.draw_sky(plane_id);
+                continue;
+            }
+            let flat = match plane.flat_id {
+                Some(flat_id) => &self.wad_data.flats.flats[flat_id].pixels,
+                None => continue,
+            };
+            let plane_height = (Fixed::from_int(plane.height) - self.view_z).abs();
+            self.plane_light = Lights::light_index(plane.light_level, 0);
+            let (min_x, max_x) = (plane.min_x, plane.max_x);
+            for x in min_x..=max_x + 1 {
+                let plane = &self.visplanes.planes[plane_id];
+                let (t1, b1) = (plane.top_at(x - 1), plane.bottom_at(x - 1));
+                let (t2, b2) = if x <= max_x {
+                    (plane.top_at(x), plane.bottom_at(x))
+                } else {
+                    (UNSET, -1)
+                };
+                self.make_spans(x, t1, b1, t2, b2, plane_height, flat);
+            }
+        }
+    }
+
+    /// Draws the columns of the sky plane from the sky texture, wrapped around
+    /// the viewer: four texture widths per turn, the middle row at the horizon.
+    fn draw_sky(&mut self, plane_id: usize) {
+        let wad_data = self.wad_data;
+        let texture = match wad_data.sky_texture {
+            Some(texture_id) => &wad_data.textures.textures[texture_id],
+            None => return,
+        };
+        let iscale = Fixed::from_int(SKY_HEIGHT) / Fixed::from_int(self.height);
+        let (min_x, max_x) = (self.visplanes.planes[plane_id].min_x, self.visplanes.planes[plane_id].max_x);
+        for x in min_x..=max_x {
+            let plane = &self.visplanes.planes[plane_id];
+            let (top, bottom) = (plane.top_at(x), plane.bottom_at(x));
+            if top > bottom {
+                continue;
+            }
+            let angle = self.view_angle + self.x_to_view_angle[x as usize];
+            let column = (angle.0 >> ANGLE_TO_SKY_SHIFT) as i32;
+            self.draw_column(x, top, bottom, texture, column, SKY_TEXTURE_MID, iscale, &wad_data.colormaps[0]);
+        }
+    }
+
+    /// Closes the rows of the previous column not continued in column `x`,
+    /// and opens the rows of column `x` not present in the previous one.
+    #[allow(clippy::too_many_arguments)]
+    fn make_spans(&mut self, x: i32, mut t1: i32, mut b1: i32, mut t2: i32, mut b2: i32, plane_height: Fixed, flat: &[u8]) {
+        while t1 < t2 && t1 <= b1 {
+            self.map_plane(t1, self.span_start[t1 as usize], x - 1, plane_height, flat);
+            t1 += 1;
+        }
+        while b1 > b2 && b1 >= t1 {
+            self.map_plane(b1, self.span_start[b1 as usize], x - 1, plane_height, flat);
+            b1 -= 1;
+        }
+        while t2 < t1 && t2 <= b2 {
+            self.span_start[t2 as usize] = x;
+            t2 += 1;
+        }
+        while b2 > b1 && b2 >= t2 {
+            self.span_start[b2 as usize] = x;
+            b2 -= 1;
+        }
+    }
+
+    /// Draws row `y` of a plane from column `x1` to `x2`. All points of a row are
+    /// at the same distance, so the flat coordinates step linearly along it.
+    fn map_plane(&mut self, y: i32, x1: i32, x2: i32, plane_height: Fixed, flat: &[u8]) {
+        let distance = plane_height * self.y_slope[y as usize];
+        let (step_x, step_y) = (distance * self.base_x_scale, distance * self.base_y_scale);
+        let length = distance * self.dist_scale[x1 as usize];
+        let angle = self.view_angle + self.x_to_view_angle[x1 as usize];
+        // Flats are stored top row first, so rows go down the map.
+        let mut flat_x = self.view_x + angle.cos() * length;
+        let mut flat_y = -self.view_y - angle.sin() * length;
+
+        let colormap = &self.wad_data.colormaps[self.lights.z_colormap(self.plane_light, distance)];
+        let mask = FLAT_SIZE as i32 - 1;
+        let row = y as usize * self.screen.pitch;
+        for x in x1..=x2 {
+            let spot = ((flat_y.0 >> FRAC_BITS) & mask) * FLAT_SIZE as i32 + ((flat_x.0 >> FRAC_BITS) & mask);
+            self.screen.pixels[row + x as usize] = colormap[flat[spot as usize] as usize];
+            flat_x += step_x;
+            flat_y += step_y;
+        }
+    }
+
+    /// Angle of the line from the viewer to a map point.
+    fn point_to_angle(&self, x: Fixed, y: Fixed) -> Angle {
+        Angle::of_vector(x - self.view_x, y - self.view_y)
+    }
+
+    /// Distance from the viewer to a map point, like `R_PointToDist`: the
+    /// longer side of the vector divided by the cosine of its angle.
+    fn point_to_dist(&self, x: Fixed, y: Fixed) -> Fixed {
+        let mut dx = (x - self.view_x).abs();
+        let mut dy = (y - self.view_y).abs();
+        if dy > dx {
+            std::mem::swap(&mut dx, &mut dy);
+        }
+        // The slope saturates when the point is the viewer.
+        let slope = ((dy / dx).0 >> SLOPE_SHIFT).min(SLOPE_RANGE as i32) as usize;
+        let angle = Angle(TAN_TO_ANGLE[slope].wrapping_add(ANG90));
+        dx / angle.sin()
+    }
+
+    /// First column right of the view angle `angle`, in `0..=width`.
+    fn angle_to_x(&self, angle: Angle) -> i32 {
+        self.view_angle_to_x[(angle.0.wrapping_add(ANG90) >> ANGLE_TO_FINE_SHIFT) as usize]
+    }
+
+    /// Projection scale of a wall point seen at the absolute angle
+    /// `vis_angle`, like `R_ScaleFromGlobalAngle`.
+    fn scale_from_global_angle(&self, vis_angle: Angle, rw_normal_angle: Angle, rw_distance: Fixed) -> Fixed {
+        let sine_a = (Angle(ANG90) + vis_angle - self.view_angle).sin();
+        let sine_b = (Angle(ANG90) + vis_angle - rw_normal_angle).sin();
+        let num = self.projection * sine_b;
+        let den = rw_distance * sine_a;
+        if den.0 > num.0 >> FRAC_BITS {
+            (num / den).clamp(MIN_SCALE, MAX_SCALE)
+        } else {
+            MAX_SCALE
+        }
+    }
+}
+
+/// Column of each view angle and view angle of each column for a view
+/// `width` columns wide with a 90 degree field of view, like
+/// `R_InitTextureMapping`.
+fn texture_mapping(width: i32, center_x: Fixed) -> (Vec<i32>, Vec<Angle>) {
+    let focal_length = center_x / Fixed(FINE_TANGENT[FINE_ANGLES / 4 + FIELD_OF_VIEW / 2]);
+    let mut view_angle_to_x: Vec<i32> = FINE_TANGENT
+        .iter()
+        .map(|&tangent| {
+            if tangent > 2 * FRAC_UNIT {
+                -1
+            } else if tangent < -2 * FRAC_UNIT {
+                width + 1
+            } else {
+                let x = center_x - Fixed(tangent) * focal_length;
+                ((x.0 + FRAC_UNIT - 1) >> FRAC_BITS).clamp(-1, width + 1)
+            }
+        })
+        .collect();
+
+    // The angle of each column is the smallest one that maps to it or left
+    // of it. Columns further right have smaller angles, so they are searched
+    // from the right.
+    let mut i = 0;
+    let mut x_to_view_angle: Vec<Angle> = (0..width)
+        .rev()
+        .map(|x| {
+            while view_angle_to_x[i] > x {
+                i += 1;
+            }
+            Angle(((i as u32) << ANGLE_TO_FINE_SHIFT).wrapping_sub(ANG90))
+        })
+        .collect();
+    x_to_view_angle.reverse();
+
+    // Angles outside of the view map to its edges.
+    for x in view_angle_to_x.iter_mut() {
+        *x = (*x).clamp(0, width);
+    }
+    (view_angle_to_x, x_to_view_angle)
+}
+
+/// Texture rows per screen row at a scale, with a full turn of precision.
+fn inverse_scale(scale: Fixed) -> Fixed {
+    Fixed((u32::MAX / scale.0.max(1) as u32) as i32)
+}
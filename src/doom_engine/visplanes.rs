@@ -0,0 +1,99 @@
+/// Top row of a visplane column that has no span.
+pub const UNSET: i32 = i32::MAX;
+
+/// A horizontal surface (floor or ceiling) seen through a range of columns,
+/// with one span of rows per column.
+pub struct Visplane {
+    pub height: i32,
+    pub flat_id: Option<usize>,
+    pub light_level: i16,
+    pub min_x: i32,
+    pub max_x: i32,
+    /// First and last row per column, indexed by `x + 1` so that the columns
+    /// next to `min_x` and `max_x` can be read as empty.
+    pub top: Vec<i32>,
+    pub bottom: Vec<i32>,
+}
+
+impl Visplane {
+    fn new(height: i32, flat_id: Option<usize>, light_level: i16, width: i32) -> Self {
+        Visplane {
+            height,
+            flat_id,
+            light_level,
+            min_x: width,
+            max_x: -1,
+            top: vec![UNSET; width as usize + 2],
+            bottom: vec![-1; width as usize + 2],
+        }
+    }
+
+    pub fn top_at(&self, x: i32) -> i32 {
+        self.top[(x + 1) as usize]
+    }
+
+    pub fn bottom_at(&self, x: i32) -> i32 {
+        self.bottom[(x + 1) as usize]
+    }
+
+    pub fn set_span(&mut self, x: i32, top: i32, bottom: i32) {
+        self.top[(x + 1) as usize] = top;
+        self.bottom[(x + 1) as usize] = bottom;
+    }
+}
+
+/// The visplanes of one frame.
+pub struct Visplanes {
+    pub planes: Vec<Visplane>,
+    width: i32,
+}
+
+impl Visplanes {
+    pub fn new(width: i32) -> Self {
+        Visplanes {
+            planes: Vec::new(),
+            width,
+        }
+    }
+
+    /// Plane with the given height, flat and light, created if needed.
+    pub fn find(&mut self, height: i32, flat_id: Option<usize>, light_level: i16) -> usize {
+        if let Some(id) = self
+            .planes
+            .iter()
+            .position(|p| p.height == height && p.flat_id == flat_id && p.light_level == light_level)
+        {
+            return id;
+        }
+        self.planes.push(Visplane::new(height, flat_id, light_level, self.width));
+        self.planes.len() - 1
+    }
+
+    /// Plane to mark columns `start..=stop` in: `plane_id` extended to cover them
+    /// if none of them is used yet, otherwise a new plane with the same surface.
+    pub fn check(&mut self, plane_id: usize, start: i32, stop: i32) -> usize {
+        let plane = &mut self.planes[plane_id];
+        let (intersect_low, union_low) = if start < plane.min_x {
+            (plane.min_x, start)
+        } else {
+            (start, plane.min_x)
+        };
+        let (intersect_high, union_high) = if stop > plane.max_x {
+            (plane.max_x, stop)
+        } else {
+            (stop, plane.max_x)
+        };
+
+        if (intersect_low..=intersect_high).all(|x| plane.top_at(x) == UNSET) {
+            plane.min_x = union_low;
+            plane.max_x = union_high;
+            return plane_id;
+        }
+
+        let mut new_plane = Visplane::new(plane.height, plane.flat_id, plane.light_level, self.width);
+        new_plane.min_x = start;
+        new_plane.max_x = stop;
+        self.planes.push(new_plane);
+        self.planes.len() - 1
+    }
+}
@@ -1,5 +1,11 @@
+use super::blockmap::Blockmap;
 use super::data_types::*;
-use super::wad_reader::WadReader;
+use super::game::GameMode;
+use super::palette::{Colormap, Palette};
+use super::reject::Reject;
+use super::sprites::Sprites;
+use super::textures::{Flat, Flats, Texture, Textures, FLAT_SIZE};
+use super::wad_reader::{name_key, name_str, Namespace, WadError, WadReader};
 
 const THINGS: usize = 1;
 const LINEDEFS: usize = 2;
@@ -12,46 +18,302 @@ const SECTORS: usize = 8;
 const REJECT: usize = 9;
 const BLOCKMAP: usize = 10;
 
+/// Ceiling flat of outdoor sectors, drawn as the sky.
+const SKY_FLAT: [u8; 8] = *b"F_SKY1\0\0";
+
 pub struct WadData {
-    reader: WadReader,
-    map_index: usize,
     pub vertexes: Vec<Vertex>,
     pub linedefs: Vec<Linedef>,
+    pub sidedefs: Vec<Sidedef>,
+    pub sectors: Vec<Sector>,
     pub nodes: Vec<Node>,
     pub sub_sectors: Vec<SubSector>,
     pub segments: Vec<Seg>,
     pub things: Vec<Thing>,
+    /// Index in `things` of the player 1 start, which every map must have.
+    player_1_start: usize,
+    pub blockmap: Blockmap,
+    pub reject: Reject,
+    pub palettes: Vec<Palette>,
+    pub colormaps: Vec<Colormap>,
+    pub textures: Textures,
+    pub flats: Flats,
+    pub sprites: Sprites,
+    /// Flat id of F_SKY1 and texture id of the sky of the map, if present.
+    pub sky_flat: Option<usize>,
+    pub sky_texture: Option<usize>,
 }
 
 impl WadData {
-    pub fn new(path: &str, map_name: &str) -> Self {
-        let mut reader = WadReader::new(path);
-        let map_index = reader
-            .directory
-            .iter()
-            .position(|d| std::str::from_utf8(&d.lump_name).unwrap() == map_name)
-            .unwrap();
+    /// Loads `map_name` from the IWAD at `iwad` with the PWADs at `pwads` stacked on
+    /// top. Without a map name the first map of the stack is loaded.
+    pub fn new(iwad: &str, pwads: &[String], map_name: Option<&str>) -> Result<Self, WadError> {
+        let mut reader = WadReader::new(iwad)?;
+        for pwad in pwads {
+            reader.add_file(pwad)?;
+        }
+        WadData::from_reader(reader, map_name)
+    }
+
+    /// Loads `map_name` from the WAD files already opened in `reader`, or the
+    /// first map of the stack without a map name.
+    pub fn from_reader(mut reader: WadReader, map_name: Option<&str>) -> Result<Self, WadError> {
+        let map_name = match map_name {
+            Some(name) => name.to_string(),
+            None => match reader.maps().first() {
+                Some(map) => map.name.to_string(),
+                None => {
+                    return Err(WadError::MapNotFound {
+                        file: reader.path().to_string(),
+                        map: String::from("any map"),
+                    })
+                }
+            },
+        };
+        let map_name = map_name.as_str();
+        let map = reader
+            .find_map(map_name)
+            .ok_or_else(|| WadError::MapNotFound {
+                file: reader.path().to_string(),
+                map: map_name.to_string(),
+            })?;
+        let map_index = map.lump_index;
         
-        let vertexes = reader.read_vertex(map_index + VERTEXES);
-        let linedefs = reader.read_linedef(map_index + LINEDEFS);
-        let nodes=reader.read_node(map_index+NODES);
-        let sub_sectors=reader.read_subsector(map_index+SSECTORS);
-        let segments = reader.read_segment(map_index+SEGS);
-        let things = reader.read_thing(map_index+THINGS);
+        let vertexes = reader.read_vertex(map_index + VERTEXES)?;
+        let linedefs = reader.read_linedef(map_index + LINEDEFS)?;
+        let sidedefs = reader.read_sidedef(map_index + SIDEDEFS)?;
+        let sectors = reader.read_sector(map_index + SECTORS)?;
+        let nodes=reader.read_node(map_index+NODES)?;
+        let sub_sectors=reader.read_subsector(map_index+SSECTORS)?;
+        let segments = reader.read_segment(map_index+SEGS)?;
+        let things = reader.read_thing(map_index+THINGS)?;
+        let player_1_start = match things.iter().position(|t| t.ttype == PLAYER_1_START) {
+            Some(i) => i,
+            None => {
+                return Err(WadError::MalformedLump {
+                    file: reader.file_path(reader.lump(map_index + THINGS)?).to_string(),
+                    lump: format!("{} THINGS", map.name),
+                    reason: "no player 1 start",
+                })
+            }
+        };
+        let blockmap = reader.read_blockmap(map_index + BLOCKMAP)?;
+        let reject = Reject::new(sectors.len(), reader.read_lump(map_index + REJECT)?);
 
+        let palettes = reader.read_palettes(reader.require_lump("PLAYPAL")?)?;
+        let colormaps = reader.read_colormaps(reader.require_lump("COLORMAP")?)?;
+        let textures = WadData::load_textures(&mut reader)?;
+        let flats = WadData::load_flats(&mut reader)?;
+        let sprites = WadData::load_sprites(&mut reader)?;
+        let sky_flat = flats.id(&SKY_FLAT);
+        let sky_texture = textures.id(&map.name.sky_texture());
         
          
         //let map_index = 0;
-        WadData {
-            reader,
-            map_index,
-           
+        Ok(WadData {
             vertexes,
             linedefs,
+            sidedefs,
+            sectors,
             nodes,
             sub_sectors,
             segments,
             things,
+            player_1_start,
+            blockmap,
+            reject,
+            palettes,
+            colormaps,
+            textures,
+            flats,
+            sprites,
+            sky_flat,
+            sky_texture,
+        })
+    }
+
+    /// Composes every texture of TEXTURE1 and TEXTURE2 from the patches named in PNAMES.
+    fn load_textures(reader: &mut WadReader) -> Result<Textures, WadError> {
+        let pnames = reader.read_pnames(reader.require_lump("PNAMES")?)?;
+        let patch_lumps = reader.namespace(Namespace::Patches);
+        let mut patches = Vec::with_capacity(pnames.len());
+        for name in pnames.iter() {
+            // Some PWADs ship patches outside of P_START / P_END.
+            let lump_index = match patch_lumps.get(&name_key(name)) {
+                Some(&i) => Some(i),
+                None => reader.find_lump(&name_str(name)),
+            };
+            let patch = match lump_index {
+                Some(lump_index) => Some(reader.read_picture(lump_index)?),
+                None => None,
+            };
+            patches.push(patch);
+        }
+
+        let mut textures = Vec::new();
+        for lump in ["TEXTURE1", "TEXTURE2"] {
+            if let Some(lump_index) = reader.find_lump(lump) {
+                for def in reader.read_texture_defs(lump_index)?.iter() {
+                    textures.push(Texture::compose(def, &patches));
+                }
+            }
+        }
+        Ok(Textures::new(textures))
+    }
+
+    fn load_flats(reader: &mut WadReader) -> Result<Flats, WadError> {
+        let mut lumps: Vec<usize> = reader.namespace(Namespace::Flats).into_values().collect();
+        lumps.sort();
+
+        let mut flats = Vec::with_capacity(lumps.len());
+        for lump_index in lumps {
+            let mut pixels = reader.read_lump(lump_index)?;
+            pixels.resize(FLAT_SIZE * FLAT_SIZE, 0);
+            flats.push(Flat {
+                name: reader.directory[lump_index].lump_name,
+                pixels,
+            });
+        }
+        Ok(Flats::new(flats))
+    }
+
+    fn load_sprites(reader: &mut WadReader) -> Result<Sprites, WadError> {
+        let mut lumps: Vec<usize> = reader.namespace(Namespace::Sprites).into_values().collect();
+        lumps.sort();
+
+        let mut pictures = Vec::with_capacity(lumps.len());
+        for lump_index in lumps {
+            let name = reader.directory[lump_index].lump_name;
+            pictures.push((name, reader.read_picture(lump_index)?));
+        }
+        Ok(Sprites::new(pictures))
+    }
+
+    pub fn front_sidedef(&self, linedef: &Linedef) -> Option<&Sidedef> {
+        self.sidedef(linedef.front_sidedef_id)
+    }
+
+    /// `None` for one-sided linedefs.
+    pub fn back_sidedef(&self, linedef: &Linedef) -> Option<&Sidedef> {
+        self.sidedef(linedef.back_sidedef_id)
+    }
+
+    pub fn front_sector(&self, linedef: &Linedef) -> Option<&Sector> {
+        self.front_sidedef(linedef)
+            .and_then(|s| self.sectors.get(s.sector_id as usize))
+    }
+
+    pub fn back_sector(&self, linedef: &Linedef) -> Option<&Sector> {
+        self.back_sidedef(linedef)
+            .and_then(|s| self.sectors.get(s.sector_id as usize))
+    }
+
+    /// Whether a monster in `sector_a` may see into `sector_b`, per the REJECT table.
+    pub fn can_see(&self, sector_a: usize, sector_b: usize) -> bool {
+        self.reject.can_see(sector_a, sector_b)
+    }
+
+    /// Where player 1 spawns: a deathmatch start in deathmatch games if the
+    /// map has one, otherwise the player 1 start.
+    pub fn player_start(&self, mode: GameMode) -> &Thing {
+        let deathmatch_start = self.things.iter().find(|t| t.ttype == DEATHMATCH_START);
+        match deathmatch_start {
+            Some(start) if mode == GameMode::Deathmatch => start,
+            _ => &self.things[self.player_1_start],
+        }
+    }
+
+    /// Sector on the side of the linedef the seg is drawn from.
+    pub fn seg_front_sector(&self, seg: &Seg) -> Option<&Sector> {
+        let linedef = &self.linedefs[seg.linedef_id as usize];
+        if seg.direction == 0 {
+            self.front_sector(linedef)
+        } else {
+            self.back_sector(linedef)
+        }
+    }
+
+    /// Sector behind the seg; `None` for one-sided linedefs.
+    pub fn seg_back_sector(&self, seg: &Seg) -> Option<&Sector> {
+        let linedef = &self.linedefs[seg.linedef_id as usize];
+        if !linedef.is_two_sided() {
+            return None;
+        }
+        if seg.direction == 0 {
+            self.back_sector(linedef)
+        } else {
+            self.front_sector(linedef)
+        }
+    }
+
+    /// Sidedef the seg is drawn with.
+    pub fn seg_sidedef(&self, seg: &Seg) -> Option<&Sidedef> {
+        let linedef = &self.linedefs[seg.linedef_id as usize];
+        if seg.direction == 0 {
+            self.front_sidedef(linedef)
+        } else {
+            self.back_sidedef(linedef)
+        }
+    }
+
+    /// Sector a subsector belongs to, taken from its first seg.
+    pub fn sub_sector_sector(&self, sub_sector_id: u16) -> Option<&Sector> {
+        self.sub_sector_sector_id(sub_sector_id)
+            .and_then(|id| self.sectors.get(id))
+    }
+
+    pub fn sub_sector_sector_id(&self, sub_sector_id: u16) -> Option<usize> {
+        let sub_sector = &self.sub_sectors[sub_sector_id as usize];
+        self.segments
+            .get(sub_sector.first_seg_id as usize)
+            .and_then(|seg| self.seg_sidedef(seg))
+            .map(|sidedef| sidedef.sector_id as usize)
+    }
+
+    /// Whether a floor or ceiling flat is the sky.
+    pub fn is_sky(&self, flat: &[u8; 8]) -> bool {
+        self.sky_flat.is_some() && self.flats.id(flat) == self.sky_flat
+    }
+
+    fn sidedef(&self, sidedef_id: u16) -> Option<&Sidedef> {
+        if sidedef_id == NO_SIDEDEF {
+            return None;
+        }
+        self.sidedefs.get(sidedef_id as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::synthetic_wad::synthetic_wad_with_things;
+
+    fn load(things: &[[i32; 5]]) -> Result<WadData, WadError> {
+        let reader = WadReader::from_bytes("test.wad", synthetic_wad_with_things(things))?;
+        WadData::from_reader(reader, Some("E1M1"))
+    }
+
+    #[test]
+    fn player_start() {
+        let wad_data = load(&[[10, 20, 90, 1, 7], [30, 40, 0, 2, 7]]).unwrap();
+        for mode in [GameMode::SinglePlayer, GameMode::Cooperative, GameMode::Deathmatch] {
+            assert_eq!(wad_data.player_start(mode).pos, (10, 20), "{mode:?}");
+        }
+    }
+
+    #[test]
+    fn deathmatch_start() {
+        let wad_data = load(&[[10, 20, 90, 1, 7], [-50, 60, 0, 11, 7], [-70, 80, 0, 11, 7]]).unwrap();
+        assert_eq!(wad_data.player_start(GameMode::Deathmatch).pos, (-50, 60));
+        assert_eq!(wad_data.player_start(GameMode::Cooperative).pos, (10, 20));
+        assert_eq!(wad_data.player_start(GameMode::SinglePlayer).pos, (10, 20));
+    }
+
+    #[test]
+    fn missing_player_1_start() {
+        for things in [&[][..], &[[30, 40, 0, 2, 7], [-50, 60, 0, 11, 7], [0, 0, 0, 2035, 7]]] {
+            let err = load(things).err().unwrap();
+            assert!(matches!(&err, WadError::MalformedLump { lump, .. } if lump == "E1M1 THINGS"), "{err}");
         }
     }
 }
@@ -1,106 +1,459 @@
+use std::collections::HashMap;
+use std::fmt;
 use std::fs::File;
+use std::io;
 use std::io::prelude::*;
 use std::io::BufReader;
 use std::io::Read;
 use std::io::SeekFrom;
 
  
+use super::blockmap::Blockmap;
 use super::data_types::*;
+use super::maps::{MapEntry, MapName};
+use super::palette::{Colormap, Palette};
 
+const HEADER_SIZE: u64 = 12;
+const DIRECTORY_ENTRY_SIZE: u64 = 16;
+
+/// Lumps that follow a map marker, in order.
+const MAP_LUMP_NAMES: [&[u8]; 10] = [
+    b"THINGS", b"LINEDEFS", b"SIDEDEFS", b"VERTEXES", b"SEGS",
+    b"SSECTORS", b"NODES", b"SECTORS", b"REJECT", b"BLOCKMAP",
+];
+
+#[derive(Debug)]
+pub enum WadError {
+    /// The file could not be opened or read.
+    Io { file: String, source: io::Error },
+    /// The header does not start with `IWAD` or `PWAD`.
+    BadMagic { file: String, magic: [u8; 4] },
+    /// The directory described by the header lies outside the file.
+    DirectoryOutOfBounds { file: String, offset: u64, size: u64, file_size: u64 },
+    /// A lump index past the end of the directory was requested.
+    LumpIndexOutOfRange { file: String, index: usize, lump_count: usize },
+    /// A lump extends past the end of the file.
+    TruncatedLump { file: String, lump: String, offset: u64, size: u64, file_size: u64 },
+    /// A lump made of fixed-size records has a size that is not a multiple of the record size.
+    BadLumpSize { file: String, lump: String, size: u64, record_size: usize },
+    /// A lump's contents do not follow the lump's format.
+    MalformedLump { file: String, lump: String, reason: &'static str },
+    /// A lump required by the engine is not in any file of the stack.
+    MissingLump { file: String, lump: String },
+    /// No map marker with the requested name exists.
+    MapNotFound { file: String, map: String },
+}
+
+impl fmt::Display for WadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WadError::Io { file, source } => write!(f, "{file}: {source}"),
+            WadError::BadMagic { file, magic } => write!(
+                f,
+                "{file}: not a WAD file (magic {:?})",
+                String::from_utf8_lossy(magic)
+            ),
+            WadError::DirectoryOutOfBounds { file, offset, size, file_size } => write!(
+                f,
+                "{file}: directory at offset {offset} ({size} bytes) lies outside the file ({file_size} bytes)"
+            ),
+            WadError::LumpIndexOutOfRange { file, index, lump_count } => write!(
+                f,
+                "{file}: lump {index} is out of range (directory has {lump_count} lumps)"
+            ),
+            WadError::TruncatedLump { file, lump, offset, size, file_size } => write!(
+                f,
+                "{file}: lump {lump} at offset {offset} ({size} bytes) is truncated (file is {file_size} bytes)"
+            ),
+            WadError::BadLumpSize { file, lump, size, record_size } => write!(
+                f,
+                "{file}: lump {lump} is {size} bytes, not a multiple of {record_size}"
+            ),
+            WadError::MalformedLump { file, lump, reason } => {
+                write!(f, "{file}: lump {lump} is malformed: {reason}")
+            }
+            WadError::MissingLump { file, lump } => write!(f, "{file}: lump {lump} not found"),
+            WadError::MapNotFound { file, map } => write!(f, "{file}: map {map} not found"),
+        }
+    }
+}
+
+impl std::error::Error for WadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            WadError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// Groups of lumps delimited by `X_START` / `X_END` markers. PWADs may also use
+/// doubled (`FF_START`) or numbered (`P1_START`) markers.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Namespace {
+    Flats,
+    Sprites,
+    Patches,
+}
+
+impl Namespace {
+    fn letter(&self) -> u8 {
+        match self {
+            Namespace::Flats => b'F',
+            Namespace::Sprites => b'S',
+            Namespace::Patches => b'P',
+        }
+    }
+
+    /// Whether `name` is a marker of this namespace ending with `suffix`.
+    fn is_marker(&self, name: &str, suffix: &str) -> bool {
+        match name.strip_suffix(suffix) {
+            Some(prefix) => {
+                let prefix = prefix.as_bytes();
+                !prefix.is_empty()
+                    && prefix.len() <= 2
+                    && prefix[0].to_ascii_uppercase() == self.letter()
+                    && prefix[1..].iter().all(|&c| c.to_ascii_uppercase() == self.letter() || c.is_ascii_digit())
+            }
+            None => false,
+        }
+    }
+}
+
+/// Anything a WAD can be read from: a file, a byte buffer, an archive entry...
+pub trait WadSource: Read + Seek {}
+
+impl<T: Read + Seek> WadSource for T {}
+
+/// A stack of WAD files: an IWAD plus any number of PWADs. `directory` is the
+/// merged directory, where later files override lumps and maps of earlier ones.
 pub struct WadReader {
     pub directory: Vec<Directory>,
-    header: Header,
-    file: BufReader<File>,
+    files: Vec<WadFile>,
+}
+
+struct WadFile {
+    source: Box<dyn WadSource>,
+    path: String,
+    size: u64,
 }
 
 struct Header {
-    wad_type: [u8; 4],
     lump_count: u32,
     init_offset: u32,
 }
 
 impl WadReader {
-    pub fn new(path: &str) -> Self {
-        let f = File::open(path).unwrap();
-        let mut file = BufReader::new(f);
-        let header = WadReader::read_header(&mut file);
-        let directory = WadReader::read_directory(&mut file, &header);
-        WadReader {
-            file,
-            header,
+    pub fn new(path: &str) -> Result<Self, WadError> {
+        let f = File::open(path).map_err(|source| WadError::Io {
+            file: path.to_string(),
+            source,
+        })?;
+        WadReader::from_reader(path, BufReader::new(f))
+    }
+
+    /// Reads a WAD held in memory. `name` is only used in error messages.
+    pub fn from_bytes(name: &str, bytes: Vec<u8>) -> Result<Self, WadError> {
+        WadReader::from_reader(name, std::io::Cursor::new(bytes))
+    }
+
+    /// Reads a WAD from any seekable source. `name` is only used in error messages.
+    pub fn from_reader<R: Read + Seek + 'static>(name: &str, mut source: R) -> Result<Self, WadError> {
+        let file_size = source
+            .seek(SeekFrom::End(0))
+            .and_then(|size| source.seek(SeekFrom::Start(0)).map(|_| size))
+            .map_err(|source| WadError::Io {
+                file: name.to_string(),
+                source,
+            })?;
+        let mut source: Box<dyn WadSource> = Box::new(source);
+        let header = WadReader::read_header(&mut source, name, file_size)?;
+        let directory = WadReader::read_directory(&mut source, &header, name, file_size)?;
+        Ok(WadReader {
             directory,
+            files: vec![WadFile {
+                source,
+                path: name.to_string(),
+                size: file_size,
+            }],
+        })
+    }
+
+    /// Opens a PWAD and stacks it on top of the files already loaded.
+    pub fn add_file(&mut self, path: &str) -> Result<(), WadError> {
+        let pwad = WadReader::new(path)?;
+        self.add_pwad(pwad);
+        Ok(())
+    }
+
+    /// Stacks the files of `pwad` on top of the files already loaded.
+    ///
+    /// Maps in `pwad` replace maps of the same name wholesale, other lumps
+    /// replace the last lump of the same name, and anything new (including
+    /// namespace markers) is appended to the directory.
+    pub fn add_pwad(&mut self, pwad: WadReader) {
+        let first_file = self.files.len();
+        self.files.extend(pwad.files);
+        let mut entries = pwad.directory;
+        for e in entries.iter_mut() {
+            e.file_index += first_file;
+        }
+
+        let mut i = 0;
+        while i < entries.len() {
+            let map_len = map_block_len(&entries, i);
+            if map_len > 0 {
+                let block = entries[i..i + map_len].to_vec();
+                let existing = (0..self.directory.len()).find(|&j| {
+                    map_block_len(&self.directory, j) > 0
+                        && self.directory[j].lump_name == block[0].lump_name
+                });
+                match existing {
+                    Some(j) => {
+                        let old_len = map_block_len(&self.directory, j);
+                        self.directory.splice(j..j + old_len, block);
+                    }
+                    None => self.directory.extend(block),
+                }
+                i += map_len;
+                continue;
+            }
+
+            let entry = entries[i].clone();
+            let existing = if entry.is_marker() {
+                None
+            } else {
+                self.directory.iter().rposition(|d| {
+                    d.lump_name == entry.lump_name && !is_map_lump_name(&d.lump_name)
+                })
+            };
+            match existing {
+                Some(j) => self.directory[j] = entry,
+                None => self.directory.push(entry),
+            }
+            i += 1;
+        }
+    }
+
+    /// All complete maps of the merged directory, in directory order.
+    pub fn maps(&self) -> Vec<MapEntry> {
+        let mut maps = Vec::new();
+        for (i, d) in self.directory.iter().enumerate() {
+            if let Some(name) = MapName::parse(&d.name()) {
+                if map_block_len(&self.directory, i) == MAP_LUMP_NAMES.len() + 1 {
+                    maps.push(MapEntry { name, lump_index: i });
+                }
+            }
         }
+        maps
+    }
+
+    /// Looks up a map by name, e.g. `e1m3` or `MAP07`.
+    pub fn find_map(&self, name: &str) -> Option<MapEntry> {
+        let name = MapName::parse(name)?;
+        self.maps().into_iter().find(|m| m.name == name)
+    }
+
+    /// Index of the last lump called `name` (case-insensitive) in the merged directory.
+    pub fn find_lump(&self, name: &str) -> Option<usize> {
+        self.directory
+            .iter()
+            .rposition(|d| d.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Indices of the lumps inside the markers of `ns`, in directory order.
+    pub fn namespace_lumps(&self, ns: Namespace) -> Vec<usize> {
+        let mut lumps = Vec::new();
+        // IWADs nest numbered sections (F1_START) inside the main one (F_START).
+        let mut depth = 0;
+        for (i, d) in self.directory.iter().enumerate() {
+            let name = d.name().to_ascii_uppercase();
+            if ns.is_marker(&name, "_START") {
+                depth += 1;
+            } else if ns.is_marker(&name, "_END") {
+                depth = (depth - 1).max(0);
+            } else if depth > 0 && !d.is_marker() {
+                lumps.push(i);
+            }
+        }
+        lumps
+    }
+
+    /// Lump indices of `ns` keyed by `name_key`. Later entries, such as lumps
+    /// added by PWADs, override earlier ones.
+    pub fn namespace(&self, ns: Namespace) -> HashMap<[u8; 8], usize> {
+        self.namespace_lumps(ns)
+            .into_iter()
+            .map(|i| (name_key(&self.directory[i].lump_name), i))
+            .collect()
+    }
+
+    /// Like `find_lump`, for lumps the engine cannot do without.
+    pub fn require_lump(&self, name: &str) -> Result<usize, WadError> {
+        self.find_lump(name).ok_or_else(|| WadError::MissingLump {
+            file: self.path().to_string(),
+            lump: name.to_string(),
+        })
+    }
+
+    /// Name of the first file of the stack, as used in error messages.
+    pub fn path(&self) -> &str {
+        &self.files[0].path
+    }
+
+    /// Name of the file a directory entry was read from.
+    pub fn file_path(&self, entry: &Directory) -> &str {
+        &self.files[entry.file_index].path
     }
 
-    fn read_header(file: &mut BufReader<File>) -> Header {
-        let mut buffer: [u8; 4] = [0; 4];
-        file.read(&mut buffer).unwrap();
-        let mut b: [u8; 4] = [0; 4];
-        file.read(&mut b).unwrap();
-        let lump_count = get_u32(&b);
-        file.read(&mut b).unwrap();
-        let init_offset = get_u32(&b);
-        Header {
-            wad_type: buffer,
+    fn read_header(file: &mut dyn WadSource, path: &str, file_size: u64) -> Result<Header, WadError> {
+        if file_size < HEADER_SIZE {
+            return Err(WadError::TruncatedLump {
+                file: path.to_string(),
+                lump: "<header>".to_string(),
+                offset: 0,
+                size: HEADER_SIZE,
+                file_size,
+            });
+        }
+        let mut b: [u8; 12] = [0; 12];
+        file.read_exact(&mut b).map_err(|source| WadError::Io {
+            file: path.to_string(),
+            source,
+        })?;
+        let wad_type = [b[0], b[1], b[2], b[3]];
+        if &wad_type != b"IWAD" && &wad_type != b"PWAD" {
+            return Err(WadError::BadMagic {
+                file: path.to_string(),
+                magic: wad_type,
+            });
+        }
+        let lump_count = get_u32(&b[4..8]);
+        let init_offset = get_u32(&b[8..12]);
+        Ok(Header {
             lump_count,
             init_offset,
-        }
+        })
     }
 
-    fn read_directory(file: &mut BufReader<File>, header: &Header) -> Vec<Directory> {
-        println!(
-            "{:?} {} {}",
-            header.wad_type, header.lump_count, header.init_offset
-        );
+    fn read_directory(
+        file: &mut dyn WadSource,
+        header: &Header,
+        path: &str,
+        file_size: u64,
+    ) -> Result<Vec<Directory>, WadError> {
+        let offset = header.init_offset as u64;
+        let size = header.lump_count as u64 * DIRECTORY_ENTRY_SIZE;
+        if offset + size > file_size {
+            return Err(WadError::DirectoryOutOfBounds {
+                file: path.to_string(),
+                offset,
+                size,
+                file_size,
+            });
+        }
+
+        let io_error = |source| WadError::Io {
+            file: path.to_string(),
+            source,
+        };
+        let mut b = vec![0; size as usize];
+        file.seek(SeekFrom::Start(offset)).map_err(io_error)?;
+        file.read_exact(&mut b).map_err(io_error)?;
+
         let mut directory = Vec::new();
-        file.seek(SeekFrom::Start(header.init_offset as u64))
-            .unwrap();
-
-        for _ in 0..header.lump_count as usize {
-            let mut b: [u8; 4] = [0; 4];
-            file.read(&mut b).unwrap();
-            let lump_offset = get_u32(&b);
-            file.read(&mut b).unwrap();
-            let lump_size = get_u32(&b);
-            let mut lump_name: [u8; 8] = [0; 8];
-            file.read(&mut lump_name).unwrap();
+        for e in b.chunks_exact(DIRECTORY_ENTRY_SIZE as usize) {
+            let lump_offset = get_u32(&e[0..4]);
+            let lump_size = get_u32(&e[4..8]);
+            let lump_name = get_name(&e[8..16]);
             directory.push(Directory {
                 lump_offset,
                 lump_size,
                 lump_name,
+                file_index: 0,
             });
         }
-        directory
+        Ok(directory)
     }
 
-    pub fn read_vertex(&mut self, lump_index: usize) -> Vec<Vertex> {
-        let count = self.directory[lump_index].lump_size / 4;
-        let offset = self.directory[lump_index].lump_offset as u64;
-        let mut b: [u8; 4] = [0; 4];
-        self.file.seek(SeekFrom::Start(offset)).unwrap();
-        let mut v = Vec::new();
-        for _ in 0..count {
-            self.file.read(&mut b).unwrap();
+    /// Returns the directory entry at `lump_index`.
+    pub fn lump(&self, lump_index: usize) -> Result<&Directory, WadError> {
+        self.directory
+            .get(lump_index)
+            .ok_or_else(|| WadError::LumpIndexOutOfRange {
+                file: self.path().to_string(),
+                index: lump_index,
+                lump_count: self.directory.len(),
+            })
+    }
+
+    /// Reads the raw contents of a lump.
+    pub fn read_lump(&mut self, lump_index: usize) -> Result<Vec<u8>, WadError> {
+        let entry = self.lump(lump_index)?;
+        let offset = entry.lump_offset as u64;
+        let size = entry.lump_size as u64;
+        let lump = entry.name();
+        let file_index = entry.file_index;
+        let file = &mut self.files[file_index];
+        if offset + size > file.size {
+            return Err(WadError::TruncatedLump {
+                file: file.path.clone(),
+                lump,
+                offset,
+                size,
+                file_size: file.size,
+            });
+        }
+
+        let mut b = vec![0; size as usize];
+        let path = &file.path;
+        let io_error = |source| WadError::Io {
+            file: path.clone(),
+            source,
+        };
+        file.source.seek(SeekFrom::Start(offset)).map_err(io_error)?;
+        file.source.read_exact(&mut b).map_err(io_error)?;
+        Ok(b)
+    }
+
+    /// Reads a lump made of `record_size`-byte records.
+    fn read_records(&mut self, lump_index: usize, record_size: usize) -> Result<Vec<u8>, WadError> {
+        let entry = self.lump(lump_index)?;
+        if !(entry.lump_size as usize).is_multiple_of(record_size) {
+            return Err(WadError::BadLumpSize {
+                file: self.file_path(entry).to_string(),
+                lump: entry.name(),
+                size: entry.lump_size as u64,
+                record_size,
+            });
+        }
+        self.read_lump(lump_index)
+    }
 
-            //let (x, y) = get_i16_tuple(&b);
-            //println!("{x} {y}");
+    fn malformed(&self, lump_index: usize, reason: &'static str) -> WadError {
+        let entry = &self.directory[lump_index];
+        WadError::MalformedLump {
+            file: self.file_path(entry).to_string(),
+            lump: entry.name(),
+            reason,
+        }
+    }
+
+    pub fn read_vertex(&mut self, lump_index: usize) -> Result<Vec<Vertex>, WadError> {
+        let b = self.read_records(lump_index, 4)?;
+        let mut v = Vec::new();
+        for b in b.chunks_exact(4) {
             let x = get_i16(&b[0..2]);
             let y = get_i16(&b[2..4]);
             v.push(Vertex { x, y });
         }
 
-        v
+        Ok(v)
     }
-    pub fn read_linedef(&mut self, lump_index: usize) -> Vec<Linedef> {
-        let count = self.directory[lump_index].lump_size / 14;
-        let offset = self.directory[lump_index].lump_offset as u64;
-        let mut b: [u8; 14] = [0; 14];
-
-        self.file.seek(SeekFrom::Start(offset)).unwrap();
+    pub fn read_linedef(&mut self, lump_index: usize) -> Result<Vec<Linedef>, WadError> {
+        let b = self.read_records(lump_index, 14)?;
         let mut v = Vec::new();
-        for _ in 0..count {
-            self.file.read(&mut b).unwrap();
-
+        for b in b.chunks_exact(14) {
             let start_vertex_id = get_u16(&b[0..2]);
             let end_vertex_id = get_u16(&b[2..4]);
             let flags = get_u16(&b[4..6]);
@@ -118,19 +471,59 @@ impl WadReader {
                 back_sidedef_id,
             });
         }
-        v
+        Ok(v)
     }
 
-    pub fn read_node(&mut self, lump_index: usize) -> Vec<Node> {
-        let count = self.directory[lump_index].lump_size / 28;
-        let offset = self.directory[lump_index].lump_offset as u64;
-        let mut b: [u8; 28] = [0; 28];
+    pub fn read_sidedef(&mut self, lump_index: usize) -> Result<Vec<Sidedef>, WadError> {
+        let b = self.read_records(lump_index, 30)?;
+        let mut v = Vec::new();
+        for b in b.chunks_exact(30) {
+            let x_offset = get_i16(&b[0..2]);
+            let y_offset = get_i16(&b[2..4]);
+            let upper_texture = get_name(&b[4..12]);
+            let lower_texture = get_name(&b[12..20]);
+            let middle_texture = get_name(&b[20..28]);
+            let sector_id = get_u16(&b[28..30]);
+            v.push(Sidedef {
+                x_offset,
+                y_offset,
+                upper_texture,
+                lower_texture,
+                middle_texture,
+                sector_id,
+            });
+        }
+        Ok(v)
+    }
 
-        self.file.seek(SeekFrom::Start(offset)).unwrap();
+    pub fn read_sector(&mut self, lump_index: usize) -> Result<Vec<Sector>, WadError> {
+        let b = self.read_records(lump_index, 26)?;
         let mut v = Vec::new();
-        for _ in 0..count {
-            self.file.read(&mut b).unwrap();
+        for b in b.chunks_exact(26) {
+            let floor_height = get_i16(&b[0..2]);
+            let ceiling_height = get_i16(&b[2..4]);
+            let floor_texture = get_name(&b[4..12]);
+            let ceiling_texture = get_name(&b[12..20]);
+            let light_level = get_i16(&b[20..22]);
+            let special = get_u16(&b[22..24]);
+            let tag = get_u16(&b[24..26]);
+            v.push(Sector {
+                floor_height,
+                ceiling_height,
+                floor_texture,
+                ceiling_texture,
+                light_level,
+                special,
+                tag,
+            });
+        }
+        Ok(v)
+    }
 
+    pub fn read_node(&mut self, lump_index: usize) -> Result<Vec<Node>, WadError> {
+        let b = self.read_records(lump_index, 28)?;
+        let mut v = Vec::new();
+        for b in b.chunks_exact(28) {
             let x_partition = get_i16(&b[0..2]);
             let y_partition = get_i16(&b[2..4]);
             let dx_partition = get_i16(&b[4..6]);
@@ -150,7 +543,6 @@ impl WadReader {
 
             let front_child_id = get_u16(&b[24..26]);
             let back_child_id = get_u16(&b[26..28]);
-            //println!("{} {} {} {}",bbox_front.top, bbox_front.bottom, bbox_front.left,bbox_front.right);
             v.push(Node {
                 x_partition,
                 y_partition,
@@ -162,20 +554,13 @@ impl WadReader {
                 back_child_id,
             });
         }
-        v
+        Ok(v)
     }
 
-    pub fn read_subsector(&mut self, lump_index: usize) -> Vec<SubSector> {
-        let count = self.directory[lump_index].lump_size / 4;
-        let offset = self.directory[lump_index].lump_offset as u64;
-        let mut b: [u8; 4] = [0; 4];
-        self.file.seek(SeekFrom::Start(offset)).unwrap();
+    pub fn read_subsector(&mut self, lump_index: usize) -> Result<Vec<SubSector>, WadError> {
+        let b = self.read_records(lump_index, 4)?;
         let mut v = Vec::new();
-        for _ in 0..count {
-            self.file.read(&mut b).unwrap();
-
-            //let (x, y) = get_i16_tuple(&b);
-            //println!("{x} {y}");
+        for b in b.chunks_exact(4) {
             let seg_count = get_u16(&b[0..2]);
             let first_seg_id = get_u16(&b[2..4]);
             v.push(SubSector {
@@ -184,18 +569,12 @@ impl WadReader {
             });
         }
 
-        v
+        Ok(v)
     }
-    pub fn read_segment(&mut self, lump_index: usize) -> Vec<Seg> {
-        let count = self.directory[lump_index].lump_size / 12;
-        let offset = self.directory[lump_index].lump_offset as u64;
-        let mut b: [u8; 12] = [0; 12];
-
-        self.file.seek(SeekFrom::Start(offset)).unwrap();
+    pub fn read_segment(&mut self, lump_index: usize) -> Result<Vec<Seg>, WadError> {
+        let b = self.read_records(lump_index, 12)?;
         let mut v = Vec::new();
-        for _ in 0..count {
-            self.file.read(&mut b).unwrap();
-
+        for b in b.chunks_exact(12) {
             let start_vertex_id = get_u16(&b[0..2]);
             let end_vertex_id = get_u16(&b[2..4]);
             let angle = get_i16(&b[4..6]);
@@ -209,21 +588,203 @@ impl WadReader {
                 linedef_id,
                 direction,
                 offset,
-              
             });
         }
-        v
+        Ok(v)
     }
 
-    pub fn read_thing(&mut self, lump_index: usize) -> Vec<Thing> {
-        let count = self.directory[lump_index].lump_size / 10;
-        let offset = self.directory[lump_index].lump_offset as u64;
-        let mut b: [u8; 10] = [0; 10];
+    /// Decodes a BLOCKMAP lump. An empty lump gives an empty blockmap.
+    pub fn read_blockmap(&mut self, lump_index: usize) -> Result<Blockmap, WadError> {
+        let b = self.read_records(lump_index, 2)?;
+        if b.is_empty() {
+            return Ok(Blockmap::empty());
+        }
+        if b.len() < 8 {
+            return Err(self.malformed(lump_index, "header is shorter than 8 bytes"));
+        }
+        let words: Vec<u16> = b.chunks_exact(2).map(get_u16).collect();
+        let origin_x = get_i16(&b[0..2]);
+        let origin_y = get_i16(&b[2..4]);
+        let columns = words[2] as usize;
+        let rows = words[3] as usize;
+        if words.len() < 4 + columns * rows {
+            return Err(self.malformed(lump_index, "block offsets extend past the end of the lump"));
+        }
+
+        let offsets = &words[4..4 + columns * rows];
+        let mut blocks = Vec::with_capacity(columns * rows);
+        for &offset in offsets {
+            // Every list starts with a 0 marker, which is skipped whatever it
+            // holds, as in the original game. A list that starts with the
+            // terminator is read as empty rather than running into the next.
+            let mut i = offset as usize;
+            if words.get(i) != Some(&0xFFFF) {
+                i += 1;
+            }
+            let mut linedefs = Vec::new();
+            loop {
+                match words.get(i) {
+                    Some(&0xFFFF) => break,
+                    Some(&linedef_id) => linedefs.push(linedef_id),
+                    None => return Err(self.malformed(lump_index, "unterminated block list")),
+                }
+                i += 1;
+            }
+            blocks.push(linedefs);
+        }
+        Ok(Blockmap {
+            origin_x,
+            origin_y,
+            columns,
+            rows,
+            blocks,
+        })
+    }
 
-        self.file.seek(SeekFrom::Start(offset)).unwrap();
+    pub fn read_palettes(&mut self, lump_index: usize) -> Result<Vec<Palette>, WadError> {
+        let b = self.read_records(lump_index, 768)?;
+        if b.is_empty() {
+            return Err(self.malformed(lump_index, "no palettes"));
+        }
         let mut v = Vec::new();
-        for _ in 0..count {
-            self.file.read(&mut b).unwrap();
+        for b in b.chunks_exact(768) {
+            let mut palette = [[0; 3]; 256];
+            for (color, rgb) in palette.iter_mut().zip(b.chunks_exact(3)) {
+                color.copy_from_slice(rgb);
+            }
+            v.push(palette);
+        }
+        Ok(v)
+    }
+
+    pub fn read_colormaps(&mut self, lump_index: usize) -> Result<Vec<Colormap>, WadError> {
+        let b = self.read_records(lump_index, 256)?;
+        if b.is_empty() {
+            return Err(self.malformed(lump_index, "no light maps"));
+        }
+        let mut v = Vec::new();
+        for b in b.chunks_exact(256) {
+            let mut colormap = [0; 256];
+            colormap.copy_from_slice(b);
+            v.push(colormap);
+        }
+        Ok(v)
+    }
+
+    /// Decodes a lump in picture format (patches, sprites, menu graphics...).
+    pub fn read_picture(&mut self, lump_index: usize) -> Result<Picture, WadError> {
+        let b = self.read_lump(lump_index)?;
+        if b.len() < 8 {
+            return Err(self.malformed(lump_index, "picture header is shorter than 8 bytes"));
+        }
+        let width = get_u16(&b[0..2]) as usize;
+        let height = get_u16(&b[2..4]) as usize;
+        let left_offset = get_i16(&b[4..6]);
+        let top_offset = get_i16(&b[6..8]);
+        if b.len() < 8 + width * 4 {
+            return Err(self.malformed(lump_index, "column offsets extend past the end of the lump"));
+        }
+
+        let mut columns = Vec::with_capacity(width);
+        for x in 0..width {
+            let mut i = get_u32(&b[8 + x * 4..12 + x * 4]) as usize;
+            let mut posts = Vec::new();
+            let mut last_top_delta = None;
+            loop {
+                let top_delta = match b.get(i) {
+                    Some(&0xFF) => break,
+                    Some(&top_delta) => top_delta as usize,
+                    None => return Err(self.malformed(lump_index, "unterminated column")),
+                };
+                // Tall patches store offsets relative to the previous post.
+                let top_delta = match last_top_delta {
+                    Some(last) if top_delta <= last => last + top_delta,
+                    _ => top_delta,
+                };
+                let length = match b.get(i + 1) {
+                    Some(&length) => length as usize,
+                    None => return Err(self.malformed(lump_index, "unterminated column")),
+                };
+                let start = i + 3;
+                if start + length > b.len() {
+                    return Err(self.malformed(lump_index, "post extends past the end of the lump"));
+                }
+                posts.push(Post {
+                    top_delta,
+                    pixels: b[start..start + length].to_vec(),
+                });
+                last_top_delta = Some(top_delta);
+                i = start + length + 1;
+            }
+            columns.push(posts);
+        }
+        Ok(Picture {
+            width,
+            height,
+            left_offset,
+            top_offset,
+            columns,
+        })
+    }
+
+    /// Reads the patch names table referenced by composite textures.
+    pub fn read_pnames(&mut self, lump_index: usize) -> Result<Vec<[u8; 8]>, WadError> {
+        let b = self.read_lump(lump_index)?;
+        let count = if b.len() >= 4 { get_u32(&b[0..4]) as usize } else { 0 };
+        if b.len() < 4 + count * 8 {
+            return Err(self.malformed(lump_index, "names extend past the end of the lump"));
+        }
+        Ok(b[4..4 + count * 8].chunks_exact(8).map(get_name).collect())
+    }
+
+    /// Reads the composite texture definitions of a TEXTURE1 or TEXTURE2 lump.
+    pub fn read_texture_defs(&mut self, lump_index: usize) -> Result<Vec<TextureDef>, WadError> {
+        let b = self.read_lump(lump_index)?;
+        let count = if b.len() >= 4 { get_u32(&b[0..4]) as usize } else { 0 };
+        if b.len() < 4 + count * 4 {
+            return Err(self.malformed(lump_index, "texture offsets extend past the end of the lump"));
+        }
+
+        let mut v = Vec::with_capacity(count);
+        for t in 0..count {
+            let offset = get_u32(&b[4 + t * 4..8 + t * 4]) as usize;
+            if offset + 22 > b.len() {
+                return Err(self.malformed(lump_index, "texture extends past the end of the lump"));
+            }
+            let d = &b[offset..];
+            let name = get_name(&d[0..8]);
+            let width = get_u16(&d[12..14]);
+            let height = get_u16(&d[14..16]);
+            if width == 0 || height == 0 {
+                // Columns wrap around the width, which must not be 0.
+                return Err(self.malformed(lump_index, "texture has no width or height"));
+            }
+            let patch_count = get_u16(&d[20..22]) as usize;
+            if offset + 22 + patch_count * 10 > b.len() {
+                return Err(self.malformed(lump_index, "texture patches extend past the end of the lump"));
+            }
+            let patches = d[22..22 + patch_count * 10]
+                .chunks_exact(10)
+                .map(|p| PatchRef {
+                    origin_x: get_i16(&p[0..2]),
+                    origin_y: get_i16(&p[2..4]),
+                    patch_id: get_u16(&p[4..6]),
+                })
+                .collect();
+            v.push(TextureDef {
+                name,
+                width,
+                height,
+                patches,
+            });
+        }
+        Ok(v)
+    }
+
+    pub fn read_thing(&mut self, lump_index: usize) -> Result<Vec<Thing>, WadError> {
+        let b = self.read_records(lump_index, 10)?;
+        let mut v = Vec::new();
+        for b in b.chunks_exact(10) {
             let pos = (get_i16(&b[0..2]),get_i16(&b[2..4]));
             let angle = get_i16(&b[4..6]);
             let ttype = get_u16(&b[6..8]);
@@ -233,14 +794,13 @@ impl WadReader {
                 angle,
                 ttype,
                 flags,
-                            
             });
         }
-        v
+        Ok(v)
     }
 }
 
-fn get_u32(b: &[u8; 4]) -> u32 {
+fn get_u32(b: &[u8]) -> u32 {
     b[0] as u32 + b[1] as u32 * 256 + b[2] as u32 * 256 * 256 + b[3] as u32 * 256 * 256 * 256
 }
 
@@ -257,8 +817,312 @@ fn get_i16(b: &[u8]) -> i16 {
     }
 }
 
+fn get_name(b: &[u8]) -> [u8; 8] {
+    let mut name: [u8; 8] = [0; 8];
+    name.copy_from_slice(&b[0..8]);
+    name
+}
+
+/// An 8-byte lump or texture name without its NUL padding.
+pub fn name_str(name: &[u8; 8]) -> String {
+    let end = name.iter().position(|&c| c == 0).unwrap_or(8);
+    String::from_utf8_lossy(&name[..end]).into_owned()
+}
+
+/// Uppercases an 8-byte lump or texture name and clears anything after the
+/// first NUL, so that names can be compared the way the original engine does.
+pub fn name_key(name: &[u8; 8]) -> [u8; 8] {
+    let mut key: [u8; 8] = [0; 8];
+    for (k, &c) in key.iter_mut().zip(name.iter().take_while(|&&c| c != 0)) {
+        *k = c.to_ascii_uppercase();
+    }
+    key
+}
+
+/// Number of directory entries (marker included) of the map starting at
+/// `index`, or 0 if `index` is not a map marker.
+fn map_block_len(directory: &[Directory], index: usize) -> usize {
+    let following = &directory[index + 1..];
+    let len = following
+        .iter()
+        .zip(MAP_LUMP_NAMES.iter())
+        .take_while(|(d, name)| d.name().as_bytes() == **name)
+        .count();
+    if len == 0 {
+        0
+    } else {
+        len + 1
+    }
+}
+
+fn is_map_lump_name(lump_name: &[u8; 8]) -> bool {
+    let end = lump_name.iter().position(|&c| c == 0).unwrap_or(8);
+    MAP_LUMP_NAMES.contains(&&lump_name[..end])
+}
+
+#[derive(Clone)]
 pub struct Directory {
     pub lump_offset: u32,
     pub lump_size: u32,
     pub lump_name: [u8; 8],
+    /// Index of the file of the stack the lump is read from.
+    pub file_index: usize,
+}
+
+impl Directory {
+    /// Lump name without the trailing NUL padding.
+    pub fn name(&self) -> String {
+        name_str(&self.lump_name)
+    }
+
+    /// Empty `*_START` / `*_END` lumps delimiting a namespace.
+    pub fn is_marker(&self) -> bool {
+        let name = self.name();
+        self.lump_size == 0 && (name.ends_with("_START") || name.ends_with("_END"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::synthetic_wad::{name8, push16, texture_defs, WadBuilder};
+
+    fn reader(wad: WadBuilder) -> WadReader {
+        WadReader::from_bytes("test.wad", wad.build()).unwrap()
+    }
+
+    #[test]
+    fn reads_records_of_a_lump() {
+        let mut wad = WadBuilder::new();
+        let mut vertexes = Vec::new();
+        push16(&mut vertexes, &[1, 2, -1, -32768]);
+        wad.lump("VERTEXES", vertexes);
+        let mut reader = reader(wad);
+
+        let v = reader.read_vertex(0).unwrap();
+        assert_eq!(v.iter().map(|v| (v.x, v.y)).collect::<Vec<_>>(), [(1, 2), (-1, -32768)]);
+    }
+
+    #[test]
+    fn truncated_header() {
+        let err = WadReader::from_bytes("test.wad", b"IWAD\x01\0\0\0".to_vec()).err().unwrap();
+        assert!(matches!(err, WadError::TruncatedLump { size: 12, file_size: 8, .. }), "{err}");
+    }
+
+    #[test]
+    fn bad_magic() {
+        let err = WadReader::from_bytes("test.wad", b"WAD2\0\0\0\0\x0c\0\0\0".to_vec()).err().unwrap();
+        assert!(matches!(err, WadError::BadMagic { magic, .. } if &magic == b"WAD2"), "{err}");
+    }
+
+    #[test]
+    fn directory_past_end_of_file() {
+        let mut wad = WadBuilder::new();
+        wad.lump("THINGS", vec![0; 10]);
+        let mut bytes = wad.build();
+        // Cut the only directory entry in half.
+        bytes.truncate(bytes.len() - 8);
+
+        let err = WadReader::from_bytes("test.wad", bytes).err().unwrap();
+        assert!(matches!(err, WadError::DirectoryOutOfBounds { offset: 22, size: 16, file_size: 30, .. }), "{err}");
+    }
+
+    #[test]
+    fn lump_past_end_of_file() {
+        let mut wad = WadBuilder::new();
+        wad.lump("THINGS", vec![0; 10]);
+        let mut bytes = wad.build();
+        // Make the lump claim 100 bytes instead of 10.
+        bytes[26..30].copy_from_slice(&100u32.to_le_bytes());
+        let mut reader = WadReader::from_bytes("test.wad", bytes).unwrap();
+
+        let err = reader.read_lump(0).err().unwrap();
+        assert!(matches!(err, WadError::TruncatedLump { offset: 12, size: 100, .. }), "{err}");
+    }
+
+    #[test]
+    fn lump_size_not_a_multiple_of_the_record_size() {
+        let mut wad = WadBuilder::new();
+        wad.lump("VERTEXES", vec![0; 6]);
+        wad.lump("LINEDEFS", vec![0; 15]);
+        let mut reader = reader(wad);
+
+        let err = reader.read_vertex(0).err().unwrap();
+        assert!(matches!(err, WadError::BadLumpSize { size: 6, record_size: 4, .. }), "{err}");
+        let err = reader.read_linedef(1).err().unwrap();
+        assert!(matches!(err, WadError::BadLumpSize { size: 15, record_size: 14, .. }), "{err}");
+    }
+
+    /// A map block whose lumps each hold one byte, `tag`.
+    fn map_block(wad: &mut WadBuilder, map: &'static str, lumps: &[&'static str], tag: u8) {
+        wad.lump(map, Vec::new());
+        for &name in lumps {
+            wad.lump(name, vec![tag]);
+        }
+    }
+
+    const MAP_LUMPS: [&str; 10] = [
+        "THINGS", "LINEDEFS", "SIDEDEFS", "VERTEXES", "SEGS",
+        "SSECTORS", "NODES", "SECTORS", "REJECT", "BLOCKMAP",
+    ];
+
+    #[test]
+    fn pwad_replaces_a_map_wholesale() {
+        let mut iwad = WadBuilder::new();
+        map_block(&mut iwad, "E1M1", &MAP_LUMPS, 1);
+        map_block(&mut iwad, "E1M2", &MAP_LUMPS, 1);
+        let mut pwad = WadBuilder::new();
+        map_block(&mut pwad, "E1M1", &MAP_LUMPS, 2);
+        // Lumps left out of a replaced map are not taken from the IWAD.
+        map_block(&mut pwad, "E1M2", &["THINGS"], 2);
+        let mut reader = reader(iwad);
+        reader.add_pwad(WadReader::from_bytes("pwad.wad", pwad.build_pwad()).unwrap());
+
+        let maps: Vec<String> = reader.maps().iter().map(|m| m.name.to_string()).collect();
+        assert_eq!(maps, ["E1M1"]);
+        let e1m1 = reader.find_map("e1m1").unwrap().lump_index;
+        let contents: Vec<u8> = (1..=MAP_LUMPS.len())
+            .map(|i| reader.read_lump(e1m1 + i).unwrap()[0])
+            .collect();
+        assert_eq!(contents, [2; 10]);
+        assert_eq!(reader.file_path(&reader.directory[e1m1]), "pwad.wad");
+    }
+
+    #[test]
+    fn pwad_adds_a_map() {
+        let mut iwad = WadBuilder::new();
+        map_block(&mut iwad, "E1M1", &MAP_LUMPS, 1);
+        let mut pwad = WadBuilder::new();
+        map_block(&mut pwad, "E1M2", &MAP_LUMPS, 2);
+        // An incomplete map cannot be loaded, on its own or over nothing.
+        map_block(&mut pwad, "E1M3", &["THINGS"], 2);
+        let mut reader = reader(iwad);
+        reader.add_pwad(WadReader::from_bytes("pwad.wad", pwad.build_pwad()).unwrap());
+
+        let maps: Vec<String> = reader.maps().iter().map(|m| m.name.to_string()).collect();
+        assert_eq!(maps, ["E1M1", "E1M2"]);
+        let e1m2 = reader.find_map("E1M2").unwrap().lump_index;
+        assert_eq!(reader.read_lump(e1m2 + 1).unwrap(), [2]);
+    }
+
+    fn blockmap_lump(words: &[i32]) -> WadReader {
+        let mut data = Vec::new();
+        push16(&mut data, words);
+        let mut wad = WadBuilder::new();
+        wad.lump("BLOCKMAP", data);
+        reader(wad)
+    }
+
+    #[test]
+    fn blockmap_with_list_markers() {
+        // 2x1 blocks at (-64, 32): linedefs 0 and 3, then no linedefs.
+        let mut reader = blockmap_lump(&[-64, 32, 2, 1, 6, 10, 0, 0, 3, -1, 0, -1]);
+        let blockmap = reader.read_blockmap(0).unwrap();
+        assert_eq!((blockmap.origin_x, blockmap.origin_y, blockmap.columns, blockmap.rows), (-64, 32, 2, 1));
+        assert_eq!(blockmap.blocks, [vec![0, 3], vec![]]);
+    }
+
+    #[test]
+    fn first_word_of_block_lists_is_skipped() {
+        // The second list starts with 7 instead of the 0 marker; the first has
+        // no marker, so its linedef 0 is taken for one.
+        let mut reader = blockmap_lump(&[0, 0, 2, 1, 6, 9, 0, 3, -1, 7, -1]);
+        let blockmap = reader.read_blockmap(0).unwrap();
+        assert_eq!(blockmap.blocks, [vec![3], vec![]]);
+
+        let mut reader = blockmap_lump(&[0, 0, 2, 1, 6, 7, -1, 0, 2, -1]);
+        let blockmap = reader.read_blockmap(0).unwrap();
+        assert_eq!(blockmap.blocks, [vec![], vec![2]]);
+    }
+
+    #[test]
+    fn empty_blockmap() {
+        let mut reader = blockmap_lump(&[]);
+        let blockmap = reader.read_blockmap(0).unwrap();
+        assert_eq!((blockmap.columns, blockmap.rows), (0, 0));
+    }
+
+    #[test]
+    fn malformed_blockmaps() {
+        for words in [&[0, 0, 1][..], &[0, 0, 2, 2, 8, 8, 8], &[0, 0, 1, 1, 5, 0, 1]] {
+            let err = blockmap_lump(words).read_blockmap(0).err().unwrap();
+            assert!(matches!(err, WadError::MalformedLump { .. }), "{err}");
+        }
+    }
+
+    #[test]
+    fn texture_definitions() {
+        let mut wad = WadBuilder::new();
+        wad.lump("TEXTURE1", texture_defs(&[("WALL", 64, 128, 0), ("WALL2", 16, 8, 1)]));
+        let defs = reader(wad).read_texture_defs(0).unwrap();
+        let sizes: Vec<_> = defs.iter().map(|d| (name_str(&d.name), d.width, d.height)).collect();
+        assert_eq!(sizes, [("WALL".to_string(), 64, 128), ("WALL2".to_string(), 16, 8)]);
+        assert_eq!(defs[1].patches[0].patch_id, 1);
+    }
+
+    #[test]
+    fn texture_without_width_or_height() {
+        for (width, height) in [(0, 128), (64, 0)] {
+            let mut wad = WadBuilder::new();
+            wad.lump("TEXTURE1", texture_defs(&[("WALL", 64, 128, 0), ("EMPTY", width, height, 0)]));
+            let err = reader(wad).read_texture_defs(0).err().unwrap();
+            assert!(matches!(err, WadError::MalformedLump { .. }), "{err}");
+        }
+    }
+
+    fn namespace_names(reader: &WadReader, ns: Namespace) -> Vec<String> {
+        let mut names: Vec<String> = reader.namespace(ns).keys().map(name_str).collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn nested_namespaces() {
+        let mut wad = WadBuilder::new();
+        for name in ["F_START", "F1_START", "FLAT1", "F1_END", "F2_START", "FLAT2", "F2_END", "F_END"] {
+            wad.lump(name, if name.starts_with("FLAT") { vec![1; 4096] } else { Vec::new() });
+        }
+        wad.lump("FLAT3", vec![1; 4096]);
+        wad.lump("S_START", Vec::new());
+        wad.lump("TROOA1", vec![1; 8]);
+        wad.lump("S_END", Vec::new());
+        let reader = reader(wad);
+
+        assert_eq!(namespace_names(&reader, Namespace::Flats), ["FLAT1", "FLAT2"]);
+        assert_eq!(namespace_names(&reader, Namespace::Sprites), ["TROOA1"]);
+        assert!(namespace_names(&reader, Namespace::Patches).is_empty());
+    }
+
+    #[test]
+    fn pwad_namespaces() {
+        let mut iwad = WadBuilder::new();
+        for name in ["F_START", "FLAT1", "FLAT2", "F_END", "S_START", "TROOA1", "S_END"] {
+            iwad.lump(name, if name.ends_with("_START") || name.ends_with("_END") { Vec::new() } else { vec![1] });
+        }
+        let mut pwad = WadBuilder::new();
+        for name in ["FF_START", "FLAT2", "FLAT9", "FF_END", "SS_START", "TROOA1", "SARGA1", "SS_END"] {
+            pwad.lump(name, if name.ends_with("_START") || name.ends_with("_END") { Vec::new() } else { vec![2] });
+        }
+        let mut reader = reader(iwad);
+        reader.add_pwad(WadReader::from_bytes("pwad.wad", pwad.build_pwad()).unwrap());
+
+        assert_eq!(namespace_names(&reader, Namespace::Flats), ["FLAT1", "FLAT2", "FLAT9"]);
+        assert_eq!(namespace_names(&reader, Namespace::Sprites), ["SARGA1", "TROOA1"]);
+        let flats = reader.namespace(Namespace::Flats);
+        for (flat, contents) in [("FLAT1", 1), ("FLAT2", 2), ("FLAT9", 2)] {
+            let lump_index = flats[&name_key(&name8(flat))];
+            assert_eq!(reader.read_lump(lump_index).unwrap(), [contents], "{flat}");
+        }
+    }
+
+    #[test]
+    fn lump_index_out_of_range() {
+        let mut wad = WadBuilder::new();
+        wad.lump("VERTEXES", vec![0; 4]);
+        let mut reader = reader(wad);
+
+        let err = reader.read_vertex(1).err().unwrap();
+        assert!(matches!(err, WadError::LumpIndexOutOfRange { index: 1, lump_count: 1, .. }), "{err}");
+        assert!(reader.read_lump(usize::MAX).is_err());
+    }
 }
@@ -0,0 +1,104 @@
+//! Golden-image tests of the renderer.
+//!
+//! Fixed camera positions in a synthetic map are rendered off-screen and
+//! compared with the reference images in `tests/golden`. After an intended
+//! change to the renderer, run the tests with `UPDATE_GOLDEN=1` to rewrite the
+//! references. When an image does not match, the rendered image and a diff
+//! (differing pixels in red over the dimmed reference) are written to
+//! `target/golden`.
+
+use std::env;
+use std::fs;
+
+use crate::doom_engine::{DoomEngine, FrameBuffer, GameMode, PixelFormat, Skill};
+use crate::image_file::Image;
+use crate::test_support::synthetic_wad;
+
+const WIDTH: usize = 192;
+const HEIGHT: usize = 120;
+/// Largest difference of a color channel for two pixels to count as equal.
+const CHANNEL_TOLERANCE: u8 = 8;
+/// Share of the pixels that may differ, to absorb floating point noise.
+const MAX_DIFFERENT_SHARE: f64 = 0.002;
+
+fn render(pos: (i16, i16), angle: i16) -> Image {
+    let mut engine = DoomEngine::from_bytes("synthetic.wad", synthetic_wad::synthetic_wad(), Some("E1M1"))
+        .expect("synthetic WAD loads");
+    engine.start_game(Skill::Medium, GameMode::SinglePlayer);
+    engine.place_player(pos, angle);
+    let mut image = Image::new(WIDTH, HEIGHT);
+    engine.draw(&mut FrameBuffer::new(&mut image.rgb, WIDTH, HEIGHT, PixelFormat::Rgb8));
+    image
+}
+
+/// Number of pixels that differ beyond the tolerance, and an image showing them.
+fn compare(expected: &Image, actual: &Image) -> (usize, Image) {
+    let mut diff = Image::new(expected.width, expected.height);
+    let mut different = 0;
+    for y in 0..expected.height {
+        for x in 0..expected.width {
+            let (e, a) = (expected.pixel(x, y), actual.pixel(x, y));
+            if e.iter().zip(a.iter()).any(|(&e, &a)| e.abs_diff(a) > CHANNEL_TOLERANCE) {
+                different += 1;
+                diff.set_pixel(x, y, [255, 0, 0]);
+            } else {
+                diff.set_pixel(x, y, [e[0] / 4, e[1] / 4, e[2] / 4]);
+            }
+        }
+    }
+    (different, diff)
+}
+
+fn check_view(name: &str, pos: (i16, i16), angle: i16) {
+    let actual = render(pos, angle);
+    let reference_path = format!("{}/tests/golden/{name}.png", env!("CARGO_MANIFEST_DIR"));
+
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::create_dir_all(format!("{}/tests/golden", env!("CARGO_MANIFEST_DIR"))).unwrap();
+        actual.save(&reference_path).unwrap();
+        return;
+    }
+
+    let expected = Image::load(&reference_path)
+        .unwrap_or_else(|err| panic!("cannot read {reference_path} ({err}); run with UPDATE_GOLDEN=1 to create it"));
+    assert_eq!(
+        (expected.width, expected.height),
+        (actual.width, actual.height),
+        "{name}: reference image has a different size"
+    );
+
+    let (different, diff) = compare(&expected, &actual);
+    let allowed = (MAX_DIFFERENT_SHARE * (WIDTH * HEIGHT) as f64) as usize;
+    if different > allowed {
+        let out_dir = format!("{}/target/golden", env!("CARGO_MANIFEST_DIR"));
+        fs::create_dir_all(&out_dir).unwrap();
+        actual.save(&format!("{out_dir}/{name}.actual.png")).unwrap();
+        diff.save(&format!("{out_dir}/{name}.diff.png")).unwrap();
+        panic!("{name}: {different} pixels differ from {reference_path} (at most {allowed} allowed); see {out_dir}");
+    }
+}
+
+#[test]
+fn start_position() {
+    check_view("start_position", (-200, 0), 0);
+}
+
+#[test]
+fn portal_at_an_angle() {
+    check_view("portal_at_an_angle", (-200, 0), 30);
+}
+
+#[test]
+fn grate_and_sprites_from_the_corner() {
+    check_view("grate_and_sprites_from_the_corner", (-100, 150), -60);
+}
+
+#[test]
+fn sky_room_looking_back() {
+    check_view("sky_room_looking_back", (200, 0), 180);
+}
+
+#[test]
+fn facing_a_wall_up_close() {
+    check_view("facing_a_wall_up_close", (-240, -170), 200);
+}
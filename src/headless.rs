@@ -0,0 +1,162 @@
+//! Rendering without a window, e.g. on build servers:
+//! `-headless -frames 8 -turn 45 -size 320 200 -out frames -format ppm`.
+//! `-palette 1` shows the frames through another PLAYPAL palette, e.g. the
+//! first damage flash.
+
+use std::fs;
+use std::io;
+use std::str::FromStr;
+
+use room::doom_engine::{DoomEngine, FrameBuffer, PixelFormat};
+use room::image_file::Image;
+
+/// Where to put the player before the first frame.
+pub enum Placement {
+    /// Keep the start position chosen by the engine.
+    Start,
+    /// On the thing with this index in THINGS.
+    Thing(usize),
+    At { x: i16, y: i16, angle: i16 },
+}
+
+/// Headless options given on the command line, next to the WAD options.
+pub struct HeadlessArgs {
+    pub frames: usize,
+    /// Degrees the player turns between frames.
+    pub turn: i16,
+    pub width: usize,
+    pub height: usize,
+    pub out_dir: String,
+    /// `png` or `ppm`.
+    pub format: String,
+    pub placement: Placement,
+    /// Render the automap instead of the first-person view.
+    pub automap: bool,
+    /// PLAYPAL palette the frames are shown with.
+    pub palette: usize,
+}
+
+impl HeadlessArgs {
+    /// Reads the headless options, with an error message for a missing or
+    /// invalid value.
+    pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut headless = HeadlessArgs {
+            frames: 1,
+            turn: 0,
+            width: 320,
+            height: 200,
+            out_dir: String::from("."),
+            format: String::from("png"),
+            placement: Placement::Start,
+            automap: false,
+            palette: 0,
+        };
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-frames" => headless.frames = value(&arg, args.next())?,
+                "-turn" => headless.turn = value(&arg, args.next())?,
+                "-size" => {
+                    headless.width = value(&arg, args.next())?;
+                    headless.height = value(&arg, args.next())?;
+                    if headless.width == 0 || headless.height == 0 {
+                        return Err(format!("{arg}: the frames cannot be empty"));
+                    }
+                }
+                "-out" => headless.out_dir = value(&arg, args.next())?,
+                "-format" => {
+                    let format: String = value(&arg, args.next())?;
+                    headless.format = format.to_ascii_lowercase();
+                    // `Image::save` picks the file format from the extension.
+                    if headless.format != "png" && headless.format != "ppm" {
+                        return Err(format!("{arg}: unknown format {format}, expected png or ppm"));
+                    }
+                }
+                "-automap" => headless.automap = true,
+                "-palette" => headless.palette = value(&arg, args.next())?,
+                "-thing" => headless.placement = Placement::Thing(value(&arg, args.next())?),
+                "-pos" => {
+                    let x = value(&arg, args.next())?;
+                    let y = value(&arg, args.next())?;
+                    let angle = value(&arg, args.next())?;
+                    headless.placement = Placement::At { x, y, angle };
+                }
+                _ => {}
+            }
+        }
+        Ok(headless)
+    }
+}
+
+/// Parses the value given after `option`. Numbers out of the range of `T`,
+/// such as map coordinates past 32767, are errors.
+fn value<T: FromStr>(option: &str, arg: Option<String>) -> Result<T, String> {
+    let arg = arg.ok_or_else(|| format!("{option}: missing value"))?;
+    arg.parse().map_err(|_| format!("{option}: invalid value {arg}"))
+}
+
+/// Renders the requested frames and writes them to `out_dir` as
+/// `frame0000.png`, `frame0001.png`... Returns the paths written.
+pub fn run(engine: &mut DoomEngine, args: &HeadlessArgs) -> io::Result<Vec<String>> {
+    match args.placement {
+        Placement::Start => {}
+        Placement::Thing(index) => {
+            if !engine.place_player_at_thing(index) {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("no thing {index} in the map")));
+            }
+        }
+        Placement::At { x, y, angle } => engine.place_player((x, y), angle),
+    }
+
+    if args.automap && !engine.automap.active {
+        engine.automap.toggle();
+    }
+    engine.set_palette(args.palette);
+
+    fs::create_dir_all(&args.out_dir)?;
+    let mut paths = Vec::with_capacity(args.frames);
+    let mut image = Image::new(args.width, args.height);
+    for frame in 0..args.frames {
+        if frame > 0 {
+            engine.turn_player(args.turn);
+        }
+        engine.draw(&mut FrameBuffer::new(&mut image.rgb, args.width, args.height, PixelFormat::Rgb8));
+
+        let path = format!("{}/frame{frame:04}.{}", args.out_dir, args.format);
+        image.save(&path)?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<HeadlessArgs, String> {
+        HeadlessArgs::parse(args.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn options() {
+        let args = parse("-headless -frames 8 -turn -45 -size 64 40 -format PPM -pos 100 -200 90 -palette 2").unwrap();
+        assert_eq!((args.frames, args.turn, args.width, args.height), (8, -45, 64, 40));
+        assert_eq!((args.format.as_str(), args.palette), ("ppm", 2));
+        assert!(matches!(args.placement, Placement::At { x: 100, y: -200, angle: 90 }));
+    }
+
+    #[test]
+    fn unknown_format() {
+        assert!(parse("-format bmp").is_err());
+        assert!(parse("-format").is_err());
+    }
+
+    #[test]
+    fn numbers_out_of_range() {
+        assert!(parse("-turn 70000").is_err());
+        assert!(parse("-pos 40000 0 0").is_err());
+        assert!(parse("-pos 0 0").is_err());
+        assert!(parse("-frames -1").is_err());
+        assert!(parse("-size 0 200").is_err());
+        assert!(parse("-thing x").is_err());
+    }
+}
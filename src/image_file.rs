@@ -0,0 +1,219 @@
+//! Minimal PNG and PPM files for frames rendered without a window.
+//!
+//! PNG data is stored uncompressed (deflate "stored" blocks), which keeps the
+//! encoder small and free of dependencies. Reading, which only supports such
+//! files, is only needed by the golden-image tests.
+
+use std::fs;
+use std::io;
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+/// Largest payload of a deflate stored block.
+const STORED_BLOCK_SIZE: usize = 65535;
+
+/// An RGB image, row by row.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub rgb: Vec<u8>,
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize) -> Self {
+        Image {
+            width,
+            height,
+            rgb: vec![0; width * height * 3],
+        }
+    }
+
+    #[cfg(test)]
+    pub fn pixel(&self, x: usize, y: usize) -> [u8; 3] {
+        let i = (y * self.width + x) * 3;
+        [self.rgb[i], self.rgb[i + 1], self.rgb[i + 2]]
+    }
+
+    #[cfg(test)]
+    pub fn set_pixel(&mut self, x: usize, y: usize, rgb: [u8; 3]) {
+        let i = (y * self.width + x) * 3;
+        self.rgb[i..i + 3].copy_from_slice(&rgb);
+    }
+
+    /// Writes a PNG or, for paths ending in `.ppm`, a binary PPM file.
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        if path.to_ascii_lowercase().ends_with(".ppm") {
+            fs::write(path, self.encode_ppm())
+        } else {
+            fs::write(path, self.encode_png())
+        }
+    }
+
+    #[cfg(test)]
+    pub fn load(path: &str) -> io::Result<Image> {
+        let bytes = fs::read(path)?;
+        let image = if bytes.starts_with(&PNG_SIGNATURE) {
+            Image::decode_png(&bytes)
+        } else {
+            Image::decode_ppm(&bytes)
+        };
+        image.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("{path}: unsupported image")))
+    }
+
+    pub fn encode_ppm(&self) -> Vec<u8> {
+        let mut out = format!("P6\n{} {}\n255\n", self.width, self.height).into_bytes();
+        out.extend_from_slice(&self.rgb);
+        out
+    }
+
+    /// Reads a binary (P6) PPM file with 8-bit channels.
+    #[cfg(test)]
+    pub fn decode_ppm(bytes: &[u8]) -> Option<Image> {
+        // Magic, width, height and maximum value, separated by whitespace.
+        let mut fields = Vec::new();
+        let mut pos = 0;
+        while fields.len() < 4 {
+            while bytes.get(pos)?.is_ascii_whitespace() {
+                pos += 1;
+            }
+            if bytes[pos] == b'#' {
+                while *bytes.get(pos)? != b'\n' {
+                    pos += 1;
+                }
+                continue;
+            }
+            let start = pos;
+            while !bytes.get(pos)?.is_ascii_whitespace() {
+                pos += 1;
+            }
+            fields.push(std::str::from_utf8(&bytes[start..pos]).ok()?);
+        }
+        if fields[0] != "P6" || fields[3] != "255" {
+            return None;
+        }
+        let width: usize = fields[1].parse().ok()?;
+        let height: usize = fields[2].parse().ok()?;
+        let rgb = bytes.get(pos + 1..pos + 1 + width * height * 3)?.to_vec();
+        Some(Image { width, height, rgb })
+    }
+
+    pub fn encode_png(&self) -> Vec<u8> {
+        let mut out = PNG_SIGNATURE.to_vec();
+
+        let mut header = Vec::with_capacity(13);
+        header.extend_from_slice(&(self.width as u32).to_be_bytes());
+        header.extend_from_slice(&(self.height as u32).to_be_bytes());
+        // 8 bits per channel, RGB, deflate, no filter, no interlace.
+        header.extend_from_slice(&[8, 2, 0, 0, 0]);
+        write_chunk(&mut out, b"IHDR", &header);
+
+        // Each row starts with its filter type, 0 (none).
+        let mut raw = Vec::with_capacity((self.width * 3 + 1) * self.height);
+        for row in self.rgb.chunks_exact(self.width * 3).take(self.height) {
+            raw.push(0);
+            raw.extend_from_slice(row);
+        }
+        let mut data = vec![0x78, 0x01];
+        let mut blocks: Vec<&[u8]> = raw.chunks(STORED_BLOCK_SIZE).collect();
+        if blocks.is_empty() {
+            blocks.push(&[]);
+        }
+        let last = blocks.len() - 1;
+        for (i, block) in blocks.into_iter().enumerate() {
+            data.push((i == last) as u8);
+            data.extend_from_slice(&(block.len() as u16).to_le_bytes());
+            data.extend_from_slice(&(!(block.len() as u16)).to_le_bytes());
+            data.extend_from_slice(block);
+        }
+        data.extend_from_slice(&adler32(&raw).to_be_bytes());
+        write_chunk(&mut out, b"IDAT", &data);
+
+        write_chunk(&mut out, b"IEND", &[]);
+        out
+    }
+
+    /// Reads an 8-bit RGB PNG whose image data is stored uncompressed and
+    /// unfiltered, such as the files written by `encode_png`.
+    #[cfg(test)]
+    pub fn decode_png(bytes: &[u8]) -> Option<Image> {
+        let mut pos = PNG_SIGNATURE.len();
+        let mut header = None;
+        let mut data = Vec::new();
+        while pos + 8 <= bytes.len() {
+            let length = u32::from_be_bytes(bytes[pos..pos + 4].try_into().ok()?) as usize;
+            let kind = &bytes[pos + 4..pos + 8];
+            let body = bytes.get(pos + 8..pos + 8 + length)?;
+            match kind {
+                b"IHDR" => header = Some(body),
+                b"IDAT" => data.extend_from_slice(body),
+                b"IEND" => break,
+                _ => {}
+            }
+            pos += 12 + length;
+        }
+
+        let header = header?;
+        let width = u32::from_be_bytes(header.get(0..4)?.try_into().ok()?) as usize;
+        let height = u32::from_be_bytes(header.get(4..8)?.try_into().ok()?) as usize;
+        if header.get(8..13)? != [8, 2, 0, 0, 0] {
+            return None;
+        }
+
+        // Skip the zlib header, then concatenate the stored blocks.
+        let mut raw = Vec::new();
+        let mut pos = 2;
+        loop {
+            let block_header = *data.get(pos)?;
+            if block_header & 0b110 != 0 {
+                return None;
+            }
+            let length = u16::from_le_bytes(data.get(pos + 1..pos + 3)?.try_into().ok()?) as usize;
+            raw.extend_from_slice(data.get(pos + 5..pos + 5 + length)?);
+            pos += 5 + length;
+            if block_header & 1 != 0 {
+                break;
+            }
+        }
+
+        // Rows written without a filter (type 0) are the pixels themselves.
+        let stride = width * 3;
+        let mut rgb = Vec::with_capacity(stride * height);
+        for y in 0..height {
+            let row = raw.get(y * (stride + 1)..(y + 1) * (stride + 1))?;
+            if row[0] != 0 {
+                return None;
+            }
+            rgb.extend_from_slice(&row[1..]);
+        }
+        Some(Image { width, height, rgb })
+    }
+}
+
+fn write_chunk(out: &mut Vec<u8>, kind: &[u8; 4], body: &[u8]) {
+    out.extend_from_slice(&(body.len() as u32).to_be_bytes());
+    let start = out.len();
+    out.extend_from_slice(kind);
+    out.extend_from_slice(body);
+    let crc = crc32(&out[start..]);
+    out.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { 0xEDB8_8320 ^ (crc >> 1) } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in bytes {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
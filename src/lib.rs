@@ -0,0 +1,9 @@
+//! A Doom renderer: loads maps from WAD files and draws the first-person view
+//! and the automap into frame buffers.
+
+pub mod doom_engine;
+pub mod image_file;
+#[cfg(test)]
+mod golden_tests;
+#[cfg(test)]
+mod test_support;
@@ -8,11 +8,111 @@ use winit_input_helper::WinitInputHelper;
 const WIDTH: u32 = 640;
 const HEIGHT: u32 = 480;
 
-mod doom_engine;
+mod headless;
 
-/// Representation of the application state. In this example, a box will bounce around the screen.
+use room::doom_engine;
+
+/// WAD files and map given on the command line, Doom style:
+/// `-iwad doom1.wad -file a.wad b.wad -map e1m3`.
+struct WadArgs {
+    iwad: String,
+    pwads: Vec<String>,
+    map: Option<String>,
+}
+
+impl WadArgs {
+    fn parse(mut args: impl Iterator<Item = String>) -> Self {
+        let mut iwad = String::from("./doom1.wad");
+        let mut pwads = Vec::new();
+        let mut map = None;
+        let mut in_file_list = false;
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-iwad" => {
+                    in_file_list = false;
+                    if let Some(path) = args.next() {
+                        iwad = path;
+                    }
+                }
+                "-map" => {
+                    in_file_list = false;
+                    map = args.next();
+                }
+                "-file" => in_file_list = true,
+                _ if arg.starts_with('-') => in_file_list = false,
+                _ if in_file_list => pwads.push(arg),
+                _ => {}
+            }
+        }
+        WadArgs { iwad, pwads, map }
+    }
+}
+
+/// Skill and game mode given with `-skill 1..5` and `-coop` or `-deathmatch`,
+/// with an error message for a missing or invalid skill.
+fn game_options(args: &[String]) -> Result<(doom_engine::Skill, doom_engine::GameMode), String> {
+    let mut skill = doom_engine::Skill::Medium;
+    let mut mode = doom_engine::GameMode::SinglePlayer;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-skill" => {
+                let number = args.next().ok_or_else(|| format!("{arg}: missing value"))?;
+                skill = number
+                    .parse()
+                    .ok()
+                    .and_then(doom_engine::Skill::from_number)
+                    .ok_or_else(|| format!("{arg}: invalid value {number}, expected 1 to 5"))?;
+            }
+            "-coop" => mode = doom_engine::GameMode::Cooperative,
+            "-deathmatch" => mode = doom_engine::GameMode::Deathmatch,
+            _ => {}
+        }
+    }
+    Ok((skill, mode))
+}
 
 fn main() -> Result<(), Error> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let wad_args = WadArgs::parse(args.iter().cloned());
+    let (skill, mode) = match game_options(&args) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("Invalid game option {err}");
+            std::process::exit(1);
+        }
+    };
+    let mut engine = match doom_engine::DoomEngine::new(
+        &wad_args.iwad,
+        &wad_args.pwads,
+        wad_args.map.as_deref(),
+    ) {
+        Ok(engine) => engine,
+        Err(err) => {
+            eprintln!("Cannot load WAD: {err}");
+            std::process::exit(1);
+        }
+    };
+    engine.start_game(skill, mode);
+
+    if args.iter().any(|arg| arg == "-headless") {
+        let headless_args = match headless::HeadlessArgs::parse(args.iter().cloned()) {
+            Ok(headless_args) => headless_args,
+            Err(err) => {
+                eprintln!("Invalid headless option {err}");
+                std::process::exit(1);
+            }
+        };
+        match headless::run(&mut engine, &headless_args) {
+            Ok(paths) => println!("Wrote {} frames to {}", paths.len(), headless_args.out_dir),
+            Err(err) => {
+                eprintln!("Cannot render frames: {err}");
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
+
     let event_loop = EventLoop::new();
     let mut input = WinitInputHelper::new();
     let window = {
@@ -30,13 +130,19 @@ fn main() -> Result<(), Error> {
         let surface_texture = SurfaceTexture::new(window_size.width, window_size.height, &window);
         Pixels::new(WIDTH, HEIGHT, surface_texture)?
     };
-    let mut engine = doom_engine::DoomEngine::new();
 
     event_loop.run(move |event, _, control_flow| {
         // Draw the current frame
         if let Event::RedrawRequested(_) = event {
-            engine.draw(pixels.frame_mut());
+            let mut frame = doom_engine::FrameBuffer::new(
+                pixels.frame_mut(),
+                WIDTH as usize,
+                HEIGHT as usize,
+                doom_engine::PixelFormat::Rgba8,
+            );
+            engine.draw(&mut frame);
             if let Err(err) = pixels.render() {
+                eprintln!("Cannot present frame: {err}");
                 *control_flow = ControlFlow::Exit;
                 return;
             }
@@ -49,6 +155,66 @@ fn main() -> Result<(), Error> {
                 *control_flow = ControlFlow::Exit;
                 return;
             }
+            if input.key_pressed(VirtualKeyCode::Tab) {
+                engine.automap.toggle();
+            }
+            if engine.automap.active {
+                if input.key_pressed(VirtualKeyCode::F) {
+                    engine.automap.follow = !engine.automap.follow;
+                }
+                if input.key_pressed(VirtualKeyCode::R) {
+                    engine.automap.rotate = !engine.automap.rotate;
+                }
+                if input.key_pressed(VirtualKeyCode::G) {
+                    engine.automap.grid = !engine.automap.grid;
+                }
+                if input.key_pressed(VirtualKeyCode::M) {
+                    engine.automap.add_mark();
+                }
+                if input.key_pressed(VirtualKeyCode::C) {
+                    engine.automap.clear_marks();
+                }
+                if input.key_pressed(VirtualKeyCode::T) {
+                    engine.automap.debug = !engine.automap.debug;
+                }
+                // Stands in for the computer area map until there are pickups.
+                if input.key_pressed(VirtualKeyCode::P) {
+                    engine.automap.computer_map = !engine.automap.computer_map;
+                }
+                if input.key_pressed(VirtualKeyCode::B) {
+                    engine.bsp_debug.toggle();
+                }
+                if input.key_pressed(VirtualKeyCode::Period) {
+                    engine.bsp_debug.step_forward();
+                }
+                if input.key_pressed(VirtualKeyCode::Comma) {
+                    engine.bsp_debug.step_back();
+                }
+            }
+            if input.key_pressed(VirtualKeyCode::Up) {
+                engine.key_state.up = true;
+            }
+            if input.key_released(VirtualKeyCode::Up) {
+                engine.key_state.up = false;
+            }
+            if input.key_pressed(VirtualKeyCode::Down) {
+                engine.key_state.down = true;
+            }
+            if input.key_released(VirtualKeyCode::Down) {
+                engine.key_state.down = false;
+            }
+            if input.key_pressed(VirtualKeyCode::Equals) {
+                engine.key_state.zoom_in = true;
+            }
+            if input.key_released(VirtualKeyCode::Equals) {
+                engine.key_state.zoom_in = false;
+            }
+            if input.key_pressed(VirtualKeyCode::Minus) {
+                engine.key_state.zoom_out = true;
+            }
+            if input.key_released(VirtualKeyCode::Minus) {
+                engine.key_state.zoom_out = false;
+            }
             if input.key_pressed(VirtualKeyCode::Left) {
                 engine.key_state.left = true;
             }
@@ -89,6 +255,7 @@ fn main() -> Result<(), Error> {
             // Resize the window
             if let Some(size) = input.window_resized() {
                 if let Err(err) = pixels.resize_surface(size.width, size.height) {
+                    eprintln!("Cannot resize window: {err}");
                     *control_flow = ControlFlow::Exit;
                     return;
                 }
@@ -100,3 +267,21 @@ fn main() -> Result<(), Error> {
         }
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<(doom_engine::Skill, doom_engine::GameMode), String> {
+        game_options(&args.split_whitespace().map(String::from).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn skill_and_mode() {
+        assert_eq!(parse("").unwrap(), (doom_engine::Skill::Medium, doom_engine::GameMode::SinglePlayer));
+        assert_eq!(parse("-skill 5 -coop").unwrap(), (doom_engine::Skill::Nightmare, doom_engine::GameMode::Cooperative));
+        assert_eq!(parse("-skill 0").unwrap_err(), "-skill: invalid value 0, expected 1 to 5");
+        assert_eq!(parse("-skill hard").unwrap_err(), "-skill: invalid value hard, expected 1 to 5");
+        assert_eq!(parse("-deathmatch -skill").unwrap_err(), "-skill: missing value");
+    }
+}
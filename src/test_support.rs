@@ -0,0 +1,3 @@
+//! Fixtures shared by the unit tests and the golden-image tests.
+
+pub(crate) mod synthetic_wad;
@@ -0,0 +1,329 @@
+//! A small IWAD built in code, so the renderer can be tested without
+//! copyrighted game data.
+//!
+//! The map, E1M1, has two rooms joined by a two-sided line at x = 0:
+//!
+//! ```text
+//!   (-256,192)-----------(0,192)
+//!       |                   |(0,128)-------------(384,128)
+//!       |     room A       grate      room B         |
+//!       |                   |(0,-128)------------(384,-128)
+//!   (-256,-192)----------(0,-192)
+//! ```
+//!
+//! Room A has a floor at 0, a ceiling at 128 and light 160. Room B is raised
+//! to 16, has a sky ceiling at 104 and light 224, so the opening shows upper
+//! and lower textures as well as a masked middle texture.
+
+/// Palette rows: 16 hues of 16 shades each, shade 15 being black.
+const HUES: [[u8; 3]; 16] = [
+    [255, 255, 255],
+    [255, 64, 64],
+    [64, 255, 64],
+    [64, 64, 255],
+    [255, 255, 64],
+    [64, 255, 255],
+    [255, 64, 255],
+    [255, 160, 64],
+    [160, 96, 48],
+    [128, 128, 128],
+    [96, 160, 255],
+    [255, 128, 160],
+    [160, 255, 128],
+    [200, 200, 160],
+    [120, 80, 200],
+    [40, 120, 40],
+];
+
+/// Palette index of a hue at a shade, 0 being the brightest.
+fn color(hue: usize, shade: usize) -> u8 {
+    (hue * 16 + shade) as u8
+}
+
+/// Collects lumps in order and writes them as a WAD. Also used by the unit
+/// tests of the WAD reader to build small or broken WADs.
+pub(crate) struct WadBuilder {
+    lumps: Vec<(&'static str, Vec<u8>)>,
+}
+
+impl WadBuilder {
+    pub(crate) fn new() -> Self {
+        WadBuilder { lumps: Vec::new() }
+    }
+
+    pub(crate) fn lump(&mut self, name: &'static str, data: Vec<u8>) {
+        self.lumps.push((name, data));
+    }
+
+    pub(crate) fn build(self) -> Vec<u8> {
+        self.build_with_magic(b"IWAD")
+    }
+
+    pub(crate) fn build_pwad(self) -> Vec<u8> {
+        self.build_with_magic(b"PWAD")
+    }
+
+    fn build_with_magic(self, magic: &[u8; 4]) -> Vec<u8> {
+        let data_len: usize = self.lumps.iter().map(|(_, data)| data.len()).sum();
+        let mut out = magic.to_vec();
+        out.extend((self.lumps.len() as u32).to_le_bytes());
+        out.extend(((12 + data_len) as u32).to_le_bytes());
+        for (_, data) in self.lumps.iter() {
+            out.extend(data);
+        }
+        let mut offset = 12;
+        for (name, data) in self.lumps.iter() {
+            out.extend((offset as u32).to_le_bytes());
+            out.extend((data.len() as u32).to_le_bytes());
+            out.extend(name8(name));
+            offset += data.len();
+        }
+        out
+    }
+}
+
+pub(crate) fn name8(name: &str) -> [u8; 8] {
+    let mut padded = [0; 8];
+    padded[..name.len()].copy_from_slice(name.as_bytes());
+    padded
+}
+
+/// Appends little-endian 16-bit values.
+pub(crate) fn push16(out: &mut Vec<u8>, values: &[i32]) {
+    for &value in values {
+        out.extend((value as i16).to_le_bytes());
+    }
+}
+
+/// Encodes a picture lump; `pixel` returns `None` for transparent pixels.
+fn picture(width: usize, height: usize, offsets: (i32, i32), pixel: impl Fn(usize, usize) -> Option<u8>) -> Vec<u8> {
+    let mut out = Vec::new();
+    push16(&mut out, &[width as i32, height as i32, offsets.0, offsets.1]);
+    let mut columns = Vec::new();
+    let columns_start = 8 + 4 * width;
+    for x in 0..width {
+        out.extend(((columns_start + columns.len()) as u32).to_le_bytes());
+        let mut y = 0;
+        while y < height {
+            if pixel(x, y).is_none() {
+                y += 1;
+                continue;
+            }
+            let top = y;
+            let mut post = Vec::new();
+            while y < height {
+                match pixel(x, y) {
+                    Some(p) => post.push(p),
+                    None => break,
+                }
+                y += 1;
+            }
+            columns.extend([top as u8, post.len() as u8, 0]);
+            columns.extend(post);
+            columns.push(0);
+        }
+        columns.push(0xFF);
+    }
+    out.extend(columns);
+    out
+}
+
+/// A TEXTURE1 lump of single-patch textures: name, size and PNAMES index.
+pub(crate) fn texture_defs(defs: &[(&str, i32, i32, i32)]) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend((defs.len() as u32).to_le_bytes());
+    let defs_start = 4 + 4 * defs.len();
+    for i in 0..defs.len() {
+        out.extend(((defs_start + i * 32) as u32).to_le_bytes());
+    }
+    for &(name, width, height, patch) in defs {
+        out.extend(name8(name));
+        out.extend(0u32.to_le_bytes());
+        push16(&mut out, &[width, height]);
+        out.extend(0u32.to_le_bytes());
+        // One patch at the origin.
+        push16(&mut out, &[1, 0, 0, patch, 1, 0]);
+    }
+    out
+}
+
+/// Things of the synthetic map: x, y, angle, type and flags (all skills).
+const THINGS: [[i32; 5]; 4] = [
+    [-200, 0, 0, 1, 7],
+    [-100, 100, 90, 2035, 7],
+    [200, -40, 180, 5, 7],
+    [-60, -120, 45, 2035, 7],
+];
+
+pub fn synthetic_wad() -> Vec<u8> {
+    synthetic_wad_with_things(&THINGS)
+}
+
+/// The synthetic WAD with other things in its map, e.g. to test starts.
+pub(crate) fn synthetic_wad_with_things(map_things: &[[i32; 5]]) -> Vec<u8> {
+    let mut wad = WadBuilder::new();
+
+    let mut playpal = Vec::new();
+    for palette in 0..14 {
+        for index in 0..256 {
+            let (hue, shade) = (index / 16, index % 16);
+            for (channel, &value) in HUES[hue].iter().enumerate() {
+                let value = (value as u32 * (15 - shade as u32) / 15) as u8;
+                // The damage and bonus palettes are tinted red.
+                let tint = if palette > 0 && channel == 0 { 40 } else { 0 };
+                playpal.push(value.saturating_add(tint));
+            }
+        }
+    }
+    wad.lump("PLAYPAL", playpal);
+
+    // Light map j darkens by j / 2 shades; then invulnerability and black.
+    let mut colormap = Vec::new();
+    for map in 0..34 {
+        for index in 0..256 {
+            let (hue, shade) = (index / 16, index % 16);
+            colormap.push(match map {
+                33 => color(0, 15),
+                32 => color(9, shade),
+                _ => color(hue, (shade + map / 2).min(15)),
+            });
+        }
+    }
+    wad.lump("COLORMAP", colormap);
+
+    wad.lump("E1M1", Vec::new());
+    let mut things = Vec::new();
+    for thing in map_things {
+        push16(&mut things, thing);
+    }
+    wad.lump("THINGS", things);
+
+    let vertexes = [(-256, -192), (-256, 192), (0, 192), (0, 128), (0, -128), (0, -192), (384, 128), (384, -128)];
+    // start, end, flags, front sidedef, back sidedef
+    let linedefs = [
+        (0, 1, 1, 0, -1),
+        (1, 2, 1, 1, -1),
+        (2, 3, 1, 2, -1),
+        (4, 5, 1, 3, -1),
+        (5, 0, 1, 4, -1),
+        (3, 4, 4, 5, 6),
+        (3, 6, 1, 7, -1),
+        (6, 7, 1, 8, -1),
+        (7, 4, 1, 9, -1),
+    ];
+    let mut data = Vec::new();
+    for (start, end, flags, front, back) in linedefs {
+        push16(&mut data, &[start, end, flags, 0, 0, front, back]);
+    }
+    wad.lump("LINEDEFS", data);
+
+    // upper, lower, middle, sector
+    let sidedefs = [
+        ("-", "-", "WALLA", 0),
+        ("-", "-", "WALLA", 0),
+        ("-", "-", "WALLA", 0),
+        ("-", "-", "WALLA", 0),
+        ("-", "-", "WALLA", 0),
+        ("WALLB", "WALLB", "GRATE", 0),
+        ("-", "-", "-", 1),
+        ("-", "-", "WALLA", 1),
+        ("-", "-", "WALLA", 1),
+        ("-", "-", "WALLA", 1),
+    ];
+    let mut data = Vec::new();
+    for (upper, lower, middle, sector) in sidedefs {
+        push16(&mut data, &[0, 0]);
+        data.extend(name8(upper));
+        data.extend(name8(lower));
+        data.extend(name8(middle));
+        push16(&mut data, &[sector]);
+    }
+    wad.lump("SIDEDEFS", data);
+
+    let mut data = Vec::new();
+    for (x, y) in vertexes {
+        push16(&mut data, &[x, y]);
+    }
+    wad.lump("VERTEXES", data);
+
+    // start, end, linedef, direction; room A first, then room B.
+    let segs = [(0, 1, 0, 0), (1, 2, 1, 0), (2, 3, 2, 0), (3, 4, 5, 0), (4, 5, 3, 0), (5, 0, 4, 0), (3, 6, 6, 0), (6, 7, 7, 0), (7, 4, 8, 0), (4, 3, 5, 1)];
+    let mut data = Vec::new();
+    for (start, end, linedef, direction) in segs {
+        let (x1, y1) = vertexes[start as usize];
+        let (x2, y2) = vertexes[end as usize];
+        let degrees = ((y2 - y1) as f64).atan2((x2 - x1) as f64).to_degrees().rem_euclid(360.0);
+        let angle = (degrees / 360.0 * 65536.0).round() as i32 & 0xFFFF;
+        push16(&mut data, &[start, end, angle, linedef, direction, 0]);
+    }
+    wad.lump("SEGS", data);
+
+    let mut data = Vec::new();
+    push16(&mut data, &[6, 0, 4, 6]);
+    wad.lump("SSECTORS", data);
+
+    // The partition runs south along x = 0: room A in front, room B behind.
+    let mut data = Vec::new();
+    push16(&mut data, &[0, 128, 0, -256, 192, -192, -256, 0, 128, -128, 0, 384, 0x8000, 0x8001]);
+    wad.lump("NODES", data);
+
+    let mut data = Vec::new();
+    for (floor, ceiling, floor_flat, ceiling_flat, light) in [(0, 128, "FLOOR", "CEIL", 160), (16, 104, "FLOOR", "F_SKY1", 224)] {
+        push16(&mut data, &[floor, ceiling]);
+        data.extend(name8(floor_flat));
+        data.extend(name8(ceiling_flat));
+        push16(&mut data, &[light, 0, 0]);
+    }
+    wad.lump("SECTORS", data);
+    wad.lump("REJECT", vec![0]);
+
+    // 5x3 blocks sharing one list with every linedef.
+    let mut data = Vec::new();
+    push16(&mut data, &[-256, -192, 5, 3]);
+    push16(&mut data, &[19; 15]);
+    push16(&mut data, &[0, 0, 1, 2, 3, 4, 5, 6, 7, 8, -1]);
+    wad.lump("BLOCKMAP", data);
+
+    wad.lump("TEXTURE1", texture_defs(&[("WALLA", 64, 128, 0), ("WALLB", 32, 64, 1), ("SKY1", 256, 128, 2), ("GRATE", 64, 48, 3)]));
+    let mut pnames = 4u32.to_le_bytes().to_vec();
+    for name in ["PATCHA", "PATCHB", "PATCHSKY", "PATCHG"] {
+        pnames.extend(name8(name));
+    }
+    wad.lump("PNAMES", pnames);
+
+    wad.lump("P_START", Vec::new());
+    wad.lump("PATCHA", picture(64, 128, (0, 0), |x, y| {
+        Some(if (x / 16 + y / 16) % 2 == 0 { color(7, 2) } else { color(8, 4) })
+    }));
+    wad.lump("PATCHB", picture(32, 64, (0, 0), |x, y| {
+        Some(if x % 8 == 0 || y % 8 == 0 { color(9, 6) } else { color(3, 3) })
+    }));
+    wad.lump("PATCHSKY", picture(256, 128, (0, 0), |x, y| {
+        Some(if y > 100 + (x % 32) / 4 { color(15, 5) } else { color(10, (y / 16).min(8)) })
+    }));
+    wad.lump("PATCHG", picture(64, 48, (0, 0), |x, y| {
+        (x % 16 < 3 || y % 16 < 3).then_some(color(11, 3))
+    }));
+    wad.lump("P_END", Vec::new());
+
+    wad.lump("F_START", Vec::new());
+    wad.lump("FLOOR", (0..4096).map(|i| if (i % 64 / 8 + i / 64 / 8) % 2 == 0 { color(9, 4) } else { color(13, 6) }).collect());
+    wad.lump("CEIL", (0..4096).map(|i| if i % 64 < 4 || i / 64 < 4 { color(4, 8) } else { color(0, 7) }).collect());
+    wad.lump("F_SKY1", vec![0; 4096]);
+    wad.lump("F_END", Vec::new());
+
+    wad.lump("S_START", Vec::new());
+    wad.lump("BAR1A0", picture(24, 32, (12, 32), |x, y| {
+        ((x as i32 - 12).abs() < 11).then_some(if y < 4 { color(2, 2) } else { color(2, 6) })
+    }));
+    // The key shows a red band whose width tells the rotation apart.
+    let key_rotations = ["BKEYA1", "BKEYA2", "BKEYA3", "BKEYA4", "BKEYA5", "BKEYA6", "BKEYA7", "BKEYA8"];
+    for (rotation, name) in key_rotations.into_iter().enumerate() {
+        wad.lump(name, picture(16, 16, (8, 16), move |x, _| {
+            Some(if x < 2 * (rotation + 1) { color(1, 1) } else { color(3, 1) })
+        }));
+    }
+    wad.lump("S_END", Vec::new());
+
+    wad.build()
+}